@@ -0,0 +1,56 @@
+// How fast the engine thinks, through the library like any other user of it.
+//
+//   cargo run --release --example bench [nodes]
+//
+// Each position is searched with a node budget, so every run does the same work and builds can
+// be compared by their times, then given a deadline to see how many futures fit in it.
+
+use rusty_snake::config::Config;
+use rusty_snake::{Budget, Engine, Turn};
+use std::time::{Duration, Instant};
+
+const POSITIONS: [(&str, &str); 2] = [
+    (
+        "duel",
+        r#"{"game": {"id": "bench-duel"}, "turn": 2, "board": {"height": 7, "width": 7, "food": [{"x": 3, "y": 3}],
+            "snakes": [{"id": "us", "name": "us", "health": 90, "body": [{"x": 0, "y": 3}, {"x": 1, "y": 3}, {"x": 2, "y": 3}]},
+                       {"id": "them", "name": "them", "health": 90, "body": [{"x": 5, "y": 5}, {"x": 5, "y": 6}, {"x": 6, "y": 6}]}]},
+            "you": {"id": "us", "name": "us", "health": 90, "body": [{"x": 0, "y": 3}, {"x": 1, "y": 3}, {"x": 2, "y": 3}]}}"#,
+    ),
+    (
+        "four",
+        r#"{"game": {"id": "bench-four"}, "turn": 20, "board": {"height": 11, "width": 11, "food": [{"x": 5, "y": 5}, {"x": 0, "y": 10}],
+            "snakes": [{"id": "us", "name": "us", "health": 80, "body": [{"x": 1, "y": 1}, {"x": 1, "y": 2}, {"x": 1, "y": 3}, {"x": 2, "y": 3}]},
+                       {"id": "b", "name": "b", "health": 70, "body": [{"x": 9, "y": 1}, {"x": 9, "y": 2}, {"x": 9, "y": 3}]},
+                       {"id": "c", "name": "c", "health": 60, "body": [{"x": 9, "y": 9}, {"x": 8, "y": 9}, {"x": 7, "y": 9}, {"x": 7, "y": 8}]},
+                       {"id": "d", "name": "d", "health": 50, "body": [{"x": 1, "y": 9}, {"x": 2, "y": 9}, {"x": 3, "y": 9}]}]},
+            "you": {"id": "us", "name": "us", "health": 80, "body": [{"x": 1, "y": 1}, {"x": 1, "y": 2}, {"x": 1, "y": 3}, {"x": 2, "y": 3}]}}"#,
+    ),
+];
+
+fn main() {
+    let nodes = match std::env::args().nth(1) {
+        Some(nodes) => nodes.parse().expect("usage: bench [nodes]"),
+        None => 20_000,
+    };
+    let engine = Engine::new(Config::default());
+    for &(name, json) in POSITIONS.iter() {
+        let turn: Turn = serde_json::from_str(json).unwrap();
+
+        let began = Instant::now();
+        let decision = engine.analyse(&turn, Budget::Nodes(nodes));
+        let took = began.elapsed();
+        println!(
+            "{:<5} analyse {} nodes: {:>7.1}ms, {:>9.0} nodes/s, {} scoring {}",
+            name,
+            decision.futures,
+            took.as_secs_f64() * 1000.0,
+            f64::from(decision.futures) / took.as_secs_f64(),
+            decision.movement,
+            decision.score()
+        );
+
+        let decision = engine.think(&turn, Instant::now() + Duration::from_millis(300));
+        println!("{:<5} think 300ms: {} nodes, {}", name, decision.futures, decision.movement);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::analysis::Grid;
+use crate::config::Aggression;
+use crate::requests::{Board, Point, Snake};
+
+// For each cell where our head can meet theirs and win.
+const HEAD_TO_HEAD_BONUS: u32 = 30;
+// For shrinking their room below their own length.
+const KILL_BONUS: u32 = 100;
+
+// Strictly longer, by at least the configured lead, and well fed enough to bother.
+fn is_prey(us: &Snake, them: &Snake, config: &Aggression) -> bool {
+    us.health >= config.min_health && us.body.len() >= them.body.len() + config.min_length_lead.max(1)
+}
+
+// Free cells next to our head that the head of a snake we're allowed to bully can also reach next turn.
+pub fn winning_collisions(board: &Board, me: usize, config: &Aggression) -> Vec<Point> {
+    let grid = Grid::new(board);
+    let us = &board.snakes[me];
+    let head = us.body[0];
+    let mut cells = vec![];
+    for (idx, snake) in board.snakes.iter().enumerate() {
+        if idx == me || !is_prey(us, snake, config) {
+            continue;
+        }
+        for next in grid.index(head).into_iter().flat_map(|h| grid.neighbours(h)) {
+            let cell = grid.point(next);
+            if grid.is_free(cell) && cell.distance(snake.body[0]) == 1 && !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+    }
+    cells
+}
+
+// Reward for chasing down snakes we're allowed to bully: get close, threaten the cells
+// they want to move into, and squeeze their reachable area below their length.
+pub fn hunt_score(board: &Board, me: usize, config: &Aggression) -> u32 {
+    let us = &board.snakes[me];
+    let head = us.body[0];
+    let grid = Grid::new(board);
+    let mut score = 0;
+    for (idx, snake) in board.snakes.iter().enumerate() {
+        if idx == me || !is_prey(us, snake, config) {
+            continue;
+        }
+        let theirs = snake.body[0];
+        score += (board.width + board.height - head.distance(theirs)).max(0) as u32;
+
+        for next in grid.index(theirs).into_iter().flat_map(|h| grid.neighbours(h)) {
+            let cell = grid.point(next);
+            if grid.is_free(cell) && cell.distance(head) == 1 {
+                score += HEAD_TO_HEAD_BONUS;
+            }
+        }
+
+        let room = grid.reachable(theirs);
+        if room < snake.body.len() as u32 {
+            score += KILL_BONUS;
+        } else {
+            score += (grid.cells() as u32).saturating_sub(room) / 4;
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake};
+
+    #[test]
+    fn finds_winning_head_to_head() {
+        let us = snake("us", 90, &[(3, 3), (2, 3), (1, 3), (0, 3)]);
+        let them = snake("them", 90, &[(3, 5), (3, 6)]);
+        let mut board = board(7, 7, vec![us, them], &[]);
+        let mut config = Aggression::default();
+        assert_eq!(winning_collisions(&board, 0, &config), vec![Point { x: 3, y: 4 }]);
+        assert!(winning_collisions(&board, 1, &config).is_empty());
+
+        config.min_length_lead = 3;
+        assert!(winning_collisions(&board, 0, &config).is_empty());
+        config.min_length_lead = 2;
+        board.snakes[0].health = config.min_health - 1;
+        assert!(winning_collisions(&board, 0, &config).is_empty());
+    }
+
+    #[test]
+    fn only_hunts_when_allowed() {
+        let config = Aggression::default();
+        let us = snake("us", 90, &[(3, 3), (2, 3), (1, 3), (0, 3)]);
+        let them = snake("them", 90, &[(3, 5), (3, 6)]);
+        let mut board = board(7, 7, vec![us, them], &[]);
+        assert!(hunt_score(&board, 0, &config) > 0);
+        assert_eq!(hunt_score(&board, 1, &config), 0);
+
+        board.snakes[0].health = config.min_health - 1;
+        assert_eq!(hunt_score(&board, 0, &config), 0);
+    }
+}
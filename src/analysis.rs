@@ -0,0 +1,290 @@
+use crate::requests::{Board, Point};
+use crate::responses::Movement;
+
+// Bonus for each opponent shut away from us into less room than its own length.
+const TRAPPED_BONUS: u32 = 50;
+
+const UNSEEN: u32 = u32::MAX;
+
+// Free cells of a board. Tails are left free, they'll have moved by the time anyone gets there.
+pub struct Grid {
+    pub width: i32,
+    pub height: i32,
+    free: Vec<bool>,
+}
+
+impl Grid {
+    pub fn new(board: &Board) -> Self {
+        let mut grid = Grid {
+            width: board.width,
+            height: board.height,
+            free: vec![true; (board.width * board.height) as usize],
+        };
+        for snake in board.snakes.iter() {
+            let keep = snake.body.len().saturating_sub(1).max(1);
+            for &piece in snake.body.iter().take(keep) {
+                grid.block(piece);
+            }
+        }
+        grid
+    }
+
+    pub fn index(&self, pos: Point) -> Option<usize> {
+        if pos.x < 0 || pos.y < 0 || pos.x >= self.width || pos.y >= self.height {
+            None
+        } else {
+            Some((pos.x + pos.y * self.width) as usize)
+        }
+    }
+
+    pub fn point(&self, idx: usize) -> Point {
+        let idx = idx as i32;
+        Point {
+            x: idx % self.width,
+            y: idx / self.width,
+        }
+    }
+
+    pub fn cells(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_free(&self, pos: Point) -> bool {
+        matches!(self.index(pos), Some(idx) if self.free[idx])
+    }
+
+    pub fn block(&mut self, pos: Point) {
+        if let Some(idx) = self.index(pos) {
+            self.free[idx] = false;
+        }
+    }
+
+    pub fn neighbours<'a>(&'a self, idx: usize) -> impl Iterator<Item = usize> + 'a {
+        let pos = self.point(idx);
+        Movement::ALL
+            .iter()
+            .filter_map(move |m| self.index(pos + m.to_offset()))
+    }
+
+    // Number of free cells reachable from start, not counting start itself.
+    pub fn reachable(&self, start: Point) -> u32 {
+        let start = match self.index(start) {
+            Some(start) => start,
+            None => return 0,
+        };
+        let mut seen = vec![false; self.cells()];
+        seen[start] = true;
+        let mut queue = vec![start];
+        let mut count = 0;
+        while let Some(idx) = queue.pop() {
+            for next in self.neighbours(idx) {
+                if self.free[next] && !seen[next] {
+                    seen[next] = true;
+                    count += 1;
+                    queue.push(next);
+                }
+            }
+        }
+        count
+    }
+
+    // Moves needed to get from start to each cell over free cells, None where there's no way.
+    pub fn distances(&self, start: Point) -> Vec<Option<u32>> {
+        let mut dist = vec![None; self.cells()];
+        let start = match self.index(start) {
+            Some(start) => start,
+            None => return dist,
+        };
+        dist[start] = Some(0);
+        let mut queue = std::collections::VecDeque::new();
+        queue.push_back(start);
+        while let Some(idx) = queue.pop_front() {
+            let d = dist[idx].unwrap();
+            for next in self.neighbours(idx) {
+                if self.free[next] && dist[next].is_none() {
+                    dist[next] = Some(d + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        dist
+    }
+}
+
+// Cut vertices and cut edges of the free cells reachable from a root (usually a head).
+pub struct Chokepoints {
+    // DFS discovery time, UNSEEN for cells the root can't reach.
+    disc: Vec<u32>,
+    pub articulation: Vec<bool>,
+    pub bridges: Vec<(usize, usize)>,
+    // How many cells get cut off from the root if this cell is blocked.
+    pub sealed: Vec<u32>,
+    // Free cells reachable from the root, not counting the root.
+    pub reachable: u32,
+}
+
+impl Chokepoints {
+    pub fn reaches(&self, idx: usize) -> bool {
+        self.disc[idx] != UNSEEN
+    }
+}
+
+// Tarjan's algorithm, iteratively so big boards don't blow the stack.
+// The root itself may be occupied (it's a head), everything else must be free.
+pub fn chokepoints(grid: &Grid, root: Point) -> Chokepoints {
+    let n = grid.cells();
+    let mut cuts = Chokepoints {
+        disc: vec![UNSEEN; n],
+        articulation: vec![false; n],
+        bridges: vec![],
+        sealed: vec![0; n],
+        reachable: 0,
+    };
+    let root = match grid.index(root) {
+        Some(root) => root,
+        None => return cuts,
+    };
+    let mut low = vec![0; n];
+    let mut size = vec![1u32; n];
+    let mut time = 1;
+    let mut root_children = 0;
+    cuts.disc[root] = 0;
+    // (cell, parent, next direction to try)
+    let mut stack = vec![(root, root, 0)];
+    while let Some(top) = stack.last_mut() {
+        let (u, parent) = (top.0, top.1);
+        if top.2 < Movement::ALL.len() {
+            let dir = Movement::ALL[top.2];
+            top.2 += 1;
+            let v = match grid.index(grid.point(u) + dir.to_offset()) {
+                Some(v) if grid.free[v] || v == root => v,
+                _ => continue,
+            };
+            if cuts.disc[v] == UNSEEN {
+                cuts.disc[v] = time;
+                low[v] = time;
+                time += 1;
+                if u == root {
+                    root_children += 1;
+                }
+                stack.push((v, u, 0));
+            } else if v != parent {
+                low[u] = low[u].min(cuts.disc[v]);
+            }
+        } else {
+            stack.pop();
+            if u == root {
+                continue;
+            }
+            low[parent] = low[parent].min(low[u]);
+            size[parent] += size[u];
+            if parent != root && low[u] >= cuts.disc[parent] {
+                cuts.articulation[parent] = true;
+                cuts.sealed[parent] += size[u];
+            }
+            if low[u] > cuts.disc[parent] {
+                cuts.bridges.push((parent, u));
+            }
+        }
+    }
+    cuts.articulation[root] = root_children > 1;
+    cuts.reachable = size[root] - 1;
+    cuts
+}
+
+// Room to move for snake `me`, less whatever an opponent could seal off with its next step,
+// plus a bonus for every opponent we've cut away from the bigger region.
+pub fn space_score(board: &Board, me: usize) -> u32 {
+    let grid = Grid::new(board);
+    let us = &board.snakes[me];
+    let cuts = chokepoints(&grid, us.body[0]);
+
+    let mut threatened = 0;
+    let mut bonus = 0;
+    for (idx, snake) in board.snakes.iter().enumerate() {
+        if idx == me {
+            continue;
+        }
+        let head = snake.body[0];
+        let mut shared = false;
+        for next in grid.index(head).into_iter().flat_map(|h| grid.neighbours(h)) {
+            if cuts.reaches(next) {
+                shared = true;
+                if cuts.articulation[next] {
+                    threatened = threatened.max(cuts.sealed[next]);
+                }
+            }
+        }
+        // Shut in with us isn't cut away from us, however little room there is.
+        if !shared {
+            let theirs = grid.reachable(head);
+            if theirs < snake.body.len() as u32 {
+                bonus += TRAPPED_BONUS;
+            } else if theirs < cuts.reachable {
+                bonus += (cuts.reachable - theirs) / 2;
+            }
+        }
+    }
+
+    let mut space = cuts.reachable.saturating_sub(threatened);
+    // It's a trap!
+    if space < us.body.len() as u32 {
+        space /= 2;
+    }
+    space + bonus
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake};
+
+    // . . # . .
+    // . . # . .
+    // . . . . .
+    fn corridor() -> Board {
+        board(5, 3, vec![snake("wall", 100, &[(2, 0), (2, 1), (2, 1)])], &[])
+    }
+
+    #[test]
+    fn finds_chokepoint() {
+        let board = corridor();
+        let grid = Grid::new(&board);
+        let cuts = chokepoints(&grid, Point { x: 0, y: 0 });
+        let gap = grid.index(Point { x: 2, y: 2 }).unwrap();
+
+        assert_eq!(cuts.reachable, 12);
+        assert!(cuts.articulation[gap]);
+        assert_eq!(cuts.sealed[gap], 6);
+        assert!(!cuts.articulation[grid.index(Point { x: 1, y: 1 }).unwrap()]);
+        assert!(cuts.bridges.contains(&(grid.index(Point { x: 1, y: 2 }).unwrap(), gap)));
+        assert!(cuts.bridges.contains(&(gap, grid.index(Point { x: 3, y: 2 }).unwrap())));
+    }
+
+    #[test]
+    fn penalizes_sealable_pocket() {
+        let mut board = corridor();
+        board.snakes.push(snake("us", 90, &[(0, 0), (0, 1)]));
+        let open = space_score(&board, 1);
+
+        // An opponent next to the gap can shut us away from the far side.
+        board.snakes.push(snake("them", 90, &[(2, 1), (1, 1)]));
+        board.snakes[0].body = vec![Point { x: 2, y: 0 }];
+        let sealable = space_score(&board, 1);
+        assert!(sealable < open);
+    }
+
+    #[test]
+    fn trapped_means_away_from_us() {
+        // Shut into the left with us, that's no win of ours.
+        let mut board = corridor();
+        board.snakes[0].body = vec![Point { x: 2, y: 0 }, Point { x: 2, y: 1 }, Point { x: 2, y: 2 }, Point { x: 2, y: 2 }];
+        board.snakes.push(snake("us", 90, &[(0, 0), (0, 1)]));
+        board.snakes.push(snake("them", 90, &[(1, 2), (1, 1), (1, 0), (1, 0)]));
+        assert!(space_score(&board, 1) < TRAPPED_BONUS);
+
+        // Shut into the right on its own, it is.
+        board.snakes[2].body = vec![Point { x: 4, y: 2 }, Point { x: 4, y: 1 }, Point { x: 4, y: 0 }, Point { x: 4, y: 0 }];
+        assert!(space_score(&board, 1) >= TRAPPED_BONUS);
+    }
+}
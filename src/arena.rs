@@ -0,0 +1,513 @@
+use crate::ratings::{self, Ratings};
+use crate::requests::{Board, Game, Snake, Turn};
+use crate::responses::Movement;
+use crate::rules::{self, Food, Ruleset};
+use crate::search::Budget;
+use crate::strategy::{self, Strategy};
+use crate::terminal::{self, Frame};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::path::Path;
+use std::process::exit;
+use std::str::FromStr;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::time::Duration;
+
+const USAGE: &str = "usage: rusty_snake arena --snake SPEC --snake SPEC... [--games N] [--width N] [--height N]
+    [--ruleset standard|solo|constrictor] [--min-food N] [--food-chance PERCENT]
+    [--nodes N] [--timeout MILLIS] [--max-turns N] [--threads N] [--seed N] [--ratings FILE] [--watch]
+SPEC is tree, tree:<config.json>, safe, the url of a snake server, or one of the bots:
+    random-safe, greedy, tail-chaser, wall-hugger, hunter, flood-fill";
+
+pub const DEFAULT_RATINGS: &str = "ratings.json";
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub width: i32,
+    pub height: i32,
+    pub ruleset: Ruleset,
+    pub food: Food,
+    pub games: u32,
+    // Call it a draw after this long.
+    pub max_turns: u32,
+    // Futures the tree search gets per move.
+    pub nodes: u32,
+    // How long snake servers get to answer.
+    pub timeout: Duration,
+    pub threads: u32,
+    pub seed: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            width: 11,
+            height: 11,
+            ruleset: Ruleset::Standard,
+            food: Food::default(),
+            games: 100,
+            max_turns: 1000,
+            nodes: 300,
+            timeout: Duration::from_millis(500),
+            threads: 4,
+            // The same every time unless asked otherwise, so any run can be repeated.
+            seed: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    // Index into the strategies, None for a draw.
+    pub winner: Option<usize>,
+    pub turns: u32,
+    // How many turns each snake lasted.
+    pub survived: Vec<u32>,
+}
+
+fn turn_for(game: &str, turn: u32, board: &Board, you: &Snake) -> Turn {
+    Turn {
+        game: Game { id: game.to_string() },
+        turn,
+        board: board.clone(),
+        you: you.clone(),
+    }
+}
+
+// Play one game to the end. Snake i is played by strategies[i].
+pub fn play<R: Rng>(settings: &Settings, strategies: &mut [Box<dyn Strategy>], game: &str, rng: &mut R) -> Outcome {
+    play_watched(settings, strategies, game, rng, None)
+}
+
+// Like play, handing `watch` every board with the moves made from it, then the final board.
+// The overlay is for snake 0.
+pub fn play_watched<R: Rng>(
+    settings: &Settings,
+    strategies: &mut [Box<dyn Strategy>],
+    game: &str,
+    rng: &mut R,
+    mut watch: Option<&mut dyn FnMut(Frame)>,
+) -> Outcome {
+    let ids: Vec<String> = (0..strategies.len()).map(|idx| idx.to_string()).collect();
+    let mut board = rules::setup(settings.width, settings.height, &ids, &settings.food, rng);
+    // How each snake last looked, for telling the dead that the game's over.
+    let mut seen = board.snakes.clone();
+    for (strategy, snake) in strategies.iter_mut().zip(seen.iter()) {
+        strategy.on_start(&turn_for(game, 0, &board, snake));
+    }
+    let outcome = play_out(settings, strategies, game, &mut board, &mut seen, rng, &mut watch);
+    if let Some(watch) = watch.as_mut() {
+        watch(frame(game, outcome.turns, &board, &[], None));
+    }
+    for (strategy, snake) in strategies.iter_mut().zip(seen.iter()) {
+        strategy.on_end(&turn_for(game, outcome.turns, &board, snake));
+    }
+    outcome
+}
+
+fn play_out<R: Rng>(
+    settings: &Settings,
+    strategies: &mut [Box<dyn Strategy>],
+    game: &str,
+    board: &mut Board,
+    seen: &mut [Snake],
+    rng: &mut R,
+    watch: &mut Option<&mut dyn FnMut(Frame)>,
+) -> Outcome {
+    let mut turn = 0;
+    let mut survived = vec![0; strategies.len()];
+    while !settings.ruleset.is_over(board) && turn < settings.max_turns {
+        let moves: Vec<_> = board
+            .snakes
+            .iter()
+            .map(|snake| {
+                let idx: usize = snake.id.parse().unwrap();
+                strategies[idx].choose_move(&turn_for(game, turn, board, snake))
+            })
+            .collect();
+        if let Some(watch) = watch.as_mut() {
+            let ours = board.snakes.iter().any(|snake| snake.id == "0");
+            let scores = strategies[0].stats().filter(|_| ours).map(|stats| stats.scores);
+            watch(frame(game, turn, board, &moves, scores));
+        }
+        let before = board.snakes.clone();
+        let dead = rules::step(board, &moves, settings.ruleset);
+        turn += 1;
+        for (snake, &died) in board.snakes.iter().zip(dead.iter()) {
+            let idx = snake.id.parse::<usize>().unwrap();
+            seen[idx] = snake.clone();
+            if !died {
+                survived[idx] = turn;
+            }
+        }
+        rules::remove_dead(board, &dead);
+        // the last one standing wins, even if it only outlasted the rest by a turn.
+        let last = match (board.snakes.len(), before.len()) {
+            (1, _) if settings.ruleset != Ruleset::Solo => Some(&board.snakes[0]),
+            (0, 1) => Some(&before[0]),
+            _ => None,
+        };
+        if let Some(snake) = last {
+            if settings.ruleset.is_over(board) {
+                return Outcome {
+                    winner: snake.id.parse().ok(),
+                    turns: turn,
+                    survived,
+                };
+            }
+        }
+        rules::spawn_food(board, &settings.food, rng);
+    }
+    Outcome {
+        winner: None,
+        turns: turn,
+        survived,
+    }
+}
+
+fn frame(game: &str, turn: u32, board: &Board, moves: &[Movement], scores: Option<[Option<u32>; 4]>) -> Frame {
+    Frame {
+        title: format!("{} turn {}", game, turn),
+        board: board.clone(),
+        you: Some("0".to_string()),
+        moves: board.snakes.iter().map(|snake| snake.id.clone()).zip(moves.iter().cloned()).collect(),
+        scores,
+    }
+}
+
+// 95% Wilson score interval for a win rate.
+pub fn wilson(wins: u32, games: u32) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+    const Z: f64 = 1.96;
+    let n = games as f64;
+    let p = wins as f64 / n;
+    let centre = p + Z * Z / (2.0 * n);
+    let spread = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt();
+    let scale = 1.0 + Z * Z / n;
+    (((centre - spread) / scale).max(0.0), ((centre + spread) / scale).min(1.0))
+}
+
+// Makes a fresh set of players, one per snake, for each thread.
+pub type Lineup = Arc<dyn Fn() -> Vec<Box<dyn Strategy>> + Send + Sync>;
+
+// Play the named strategies against each other, see play_all.
+pub fn run<F: FnMut(&Outcome)>(settings: &Settings, specs: &[String], each: F) -> Result<Vec<Outcome>, String> {
+    let budget = Budget::Nodes(settings.nodes);
+    let timeout = settings.timeout;
+    // Catch bad specs before there's a thread to die in.
+    for spec in specs {
+        strategy::by_name(spec, budget, timeout)?;
+    }
+    let specs = specs.to_vec();
+    let lineup: Lineup = Arc::new(move || {
+        specs
+            .iter()
+            .map(|spec| strategy::by_name(spec, budget, timeout).unwrap())
+            .collect()
+    });
+    play_all(settings, lineup, each)
+}
+
+// Play all the games over a few threads, handing each outcome to `each` as it comes in.
+pub fn play_all<F: FnMut(&Outcome)>(settings: &Settings, lineup: Lineup, mut each: F) -> Result<Vec<Outcome>, String> {
+    let (send, recv) = channel();
+    let threads = settings.threads.max(1);
+    let mut handles = vec![];
+    for thread in 0..threads {
+        let send = send.clone();
+        let settings = settings.clone();
+        let lineup = lineup.clone();
+        handles.push(std::thread::spawn(move || {
+            let mut strategies = lineup();
+            for game in (thread..settings.games).step_by(threads as usize) {
+                // Each game gets its own seed, so any one of them can be played again.
+                let mut rng = StdRng::seed_from_u64(settings.seed.wrapping_add(game as u64));
+                let outcome = play(&settings, &mut strategies, &format!("arena-{}", game), &mut rng);
+                if send.send(outcome).is_err() {
+                    return;
+                }
+            }
+        }));
+    }
+    drop(send);
+    let outcomes = recv.iter().inspect(|outcome| each(outcome)).collect();
+    for handle in handles {
+        handle.join().map_err(|_| "an arena thread panicked".to_string())?;
+    }
+    Ok(outcomes)
+}
+
+fn value<T: FromStr>(arg: Option<&String>) -> T {
+    parsed(arg, USAGE)
+}
+
+// The value after a command line flag, or the usage if it's missing or nonsense.
+pub fn parsed<T: FromStr>(arg: Option<&String>, usage: &str) -> T {
+    match arg.and_then(|arg| arg.parse().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("{}", usage);
+            exit(2);
+        }
+    }
+}
+
+pub fn main(args: &[String]) {
+    let mut settings = Settings::default();
+    let mut specs = vec![];
+    let mut ratings_path = DEFAULT_RATINGS.to_string();
+    let mut watch = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--snake" => specs.push(value::<String>(args.next())),
+            "--games" => settings.games = value(args.next()),
+            "--width" => settings.width = value(args.next()),
+            "--height" => settings.height = value(args.next()),
+            "--ruleset" => settings.ruleset = value(args.next()),
+            "--min-food" => settings.food.min = value(args.next()),
+            "--food-chance" => settings.food.chance = value(args.next()),
+            "--nodes" => settings.nodes = value(args.next()),
+            "--timeout" => settings.timeout = Duration::from_millis(value(args.next())),
+            "--max-turns" => settings.max_turns = value(args.next()),
+            "--threads" => settings.threads = value(args.next()),
+            "--seed" => settings.seed = value(args.next()),
+            "--ratings" => ratings_path = value(args.next()),
+            "--watch" => watch = true,
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(2);
+            }
+        }
+    }
+    if specs.is_empty() || settings.width < 2 || settings.height < 2 {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+
+    if watch {
+        return watch_one(&settings, &specs);
+    }
+
+    println!(
+        "{} games of {:?} on {}x{}, seed {}",
+        settings.games, settings.ruleset, settings.width, settings.height, settings.seed
+    );
+    let fail = |e: String| -> ! {
+        eprintln!("{}", e);
+        exit(1);
+    };
+    let players: Vec<String> = specs
+        .iter()
+        .map(|spec| strategy::identity(spec))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| fail(e));
+    let ratings_path = Path::new(&ratings_path);
+    let mut ratings = Ratings::load(ratings_path).unwrap_or_else(|e| fail(format!("{}: {}", ratings_path.display(), e)));
+    let outcomes = run(&settings, &specs, |outcome| ratings.update(&players, &outcome.survived, outcome.winner))
+        .unwrap_or_else(|e| fail(e));
+    if let Err(e) = ratings.save(ratings_path) {
+        eprintln!("Can't save ratings to {}: {}", ratings_path.display(), e);
+    }
+    let games = outcomes.len() as u32;
+    let mut wins = vec![0; specs.len()];
+    let mut draws = 0;
+    for outcome in outcomes.iter() {
+        match outcome.winner {
+            Some(idx) => wins[idx] += 1,
+            None => draws += 1,
+        }
+    }
+    for (idx, spec) in specs.iter().enumerate() {
+        let (low, high) = wilson(wins[idx], games);
+        println!(
+            "{:>2} {:<30} {:>6} wins  {:>5.1}%  (95%: {:.1}% - {:.1}%)",
+            idx,
+            spec,
+            wins[idx],
+            100.0 * wins[idx] as f64 / games.max(1) as f64,
+            100.0 * low,
+            100.0 * high
+        );
+    }
+    let turns: u32 = outcomes.iter().map(|outcome| outcome.turns).sum();
+    println!("{} draws, {:.1} turns a game", draws, turns as f64 / games.max(1) as f64);
+    println!();
+    ratings::report(&ratings);
+}
+
+// Play the first game of the series and step through it.
+fn watch_one(settings: &Settings, specs: &[String]) {
+    let budget = Budget::Nodes(settings.nodes);
+    let mut strategies: Vec<Box<dyn Strategy>> = specs
+        .iter()
+        .map(|spec| strategy::by_name(spec, budget, settings.timeout))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        });
+    let mut frames = vec![];
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+    let outcome = play_watched(settings, &mut strategies, "arena-0", &mut rng, Some(&mut |frame| frames.push(frame)));
+    if let Err(e) = terminal::step_through(&frames) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+    match outcome.winner {
+        Some(idx) => println!("{} won after {} turns", specs[idx], outcome.turns),
+        None => println!("draw after {} turns", outcome.turns),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wilson_bounds() {
+        let (low, high) = wilson(50, 100);
+        assert!(low > 0.39 && low < 0.41);
+        assert!(high > 0.59 && high < 0.61);
+        assert_eq!(wilson(0, 10).0, 0.0);
+        assert_eq!(wilson(10, 10).1, 1.0);
+    }
+
+    #[test]
+    fn games_finish() {
+        let settings = Settings {
+            width: 7,
+            height: 7,
+            games: 4,
+            max_turns: 300,
+            threads: 2,
+            seed: 7,
+            ..Settings::default()
+        };
+        let specs = vec!["safe".to_string(), "safe".to_string()];
+        let mut seen = 0;
+        let outcomes = run(&settings, &specs, |_| seen += 1).unwrap();
+        assert_eq!(outcomes.len(), 4);
+        assert_eq!(seen, 4);
+        for outcome in outcomes.iter() {
+            assert!(outcome.turns <= 300);
+            if let Some(winner) = outcome.winner {
+                assert_eq!(outcome.survived[winner], outcome.turns);
+                assert!(outcome.survived[1 - winner] < outcome.turns);
+            }
+        }
+        assert!(run(&settings, &["nonsense".to_string()], |_| ()).is_err());
+    }
+
+    #[test]
+    fn watches_every_turn() {
+        let settings = Settings {
+            width: 7,
+            height: 7,
+            max_turns: 50,
+            seed: 5,
+            ..Settings::default()
+        };
+        let mut strategies = vec![
+            strategy::by_name("tree", Budget::Nodes(50), settings.timeout).unwrap(),
+            strategy::by_name("safe", Budget::Nodes(50), settings.timeout).unwrap(),
+        ];
+        let mut frames = vec![];
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        let outcome = play_watched(&settings, &mut strategies, "watched", &mut rng, Some(&mut |frame| frames.push(frame)));
+        assert_eq!(frames.len() as u32, outcome.turns + 1);
+        assert_eq!(frames[0].moves.len(), 2);
+        assert!(frames[0].scores.is_some());
+        assert!(frames.last().unwrap().moves.is_empty());
+    }
+
+    // A snake server that gives the nth move from `answers` (and the last one after that)
+    // after however many milliseconds it says.
+    fn serve(answers: &'static [(&'static str, u64)]) -> String {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let mut moves = 0;
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 8192];
+                let n = stream.read(&mut request).unwrap();
+                let (answer, delay) = if String::from_utf8_lossy(&request[..n]).starts_with("POST /move") {
+                    moves += 1;
+                    answers[(moves - 1).min(answers.len() - 1)]
+                } else {
+                    ("up", 0)
+                };
+                // Answered on the side, so a late answer doesn't hold up the next request.
+                std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(delay));
+                    let body = format!("{{\"move\":\"{}\"}}", answer);
+                    let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+                });
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn late_answers_repeat_the_last_move() {
+        let settings = Settings {
+            width: 11,
+            height: 11,
+            max_turns: 100,
+            timeout: Duration::from_millis(100),
+            seed: 3,
+            ..Settings::default()
+        };
+        let prompt = strategy::by_name(&serve(&[("left", 0)]), Budget::Nodes(0), settings.timeout);
+        // Answers the first move in time, then only ever too late.
+        let late = strategy::by_name(&serve(&[("right", 0), ("down", 300)]), Budget::Nodes(0), settings.timeout);
+        let mut strategies = vec![prompt.unwrap(), late.unwrap()];
+        let mut frames = vec![];
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        play_watched(&settings, &mut strategies, "remote", &mut rng, Some(&mut |frame| frames.push(frame)));
+        let moves = |id: &str| -> Vec<Movement> {
+            frames.iter().flat_map(|frame| frame.moves.iter().filter(|(snake, _)| snake == id).map(|&(_, dir)| dir)).collect()
+        };
+        let (prompt, late) = (moves("0"), moves("1"));
+        assert!(!prompt.is_empty() && prompt.iter().all(|&dir| dir == Movement::Left));
+        // Right when it answered, and right again every time it was late.
+        assert!(late.len() > 1, "{:?}", late);
+        assert!(late.iter().all(|&dir| dir == Movement::Right));
+    }
+
+    // The engines here are shell scripts.
+    #[cfg(unix)]
+    #[test]
+    fn plays_engines_over_stdio() {
+        let settings = Settings {
+            width: 7,
+            height: 7,
+            max_turns: 100,
+            seed: 3,
+            ..Settings::default()
+        };
+        // Answers left to every go, after a right for some other turn, and says nothing to anything else.
+        let script = r#"while read line; do case "$line" in
+            position*) turn=$(echo "$line" | sed 's/.*"turn":\([0-9]*\).*/\1/');;
+            go*) echo '{"type":"bestmove","move":"right","turn":9999}'; echo "{\"type\":\"bestmove\",\"move\":\"left\",\"turn\":$turn}";;
+        esac; done"#;
+        let command: Vec<String> = vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+        let engine = strategy::Subprocess::new(&command, Budget::Nodes(10), settings.timeout).unwrap();
+        let silent = strategy::by_name("exec:cat", Budget::Nodes(10), Duration::from_millis(50)).unwrap();
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(engine), silent];
+        let mut frames = vec![];
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        let outcome = play_watched(&settings, &mut strategies, "stdio", &mut rng, Some(&mut |frame| frames.push(frame)));
+        // Always left, and always up for the one that never answers: into a wall either way.
+        assert!(outcome.turns <= 7);
+        let moves: Vec<Movement> =
+            frames.iter().flat_map(|frame| frame.moves.iter().filter(|(snake, _)| snake == "0").map(|&(_, dir)| dir)).collect();
+        assert!(!moves.is_empty() && moves.iter().all(|&dir| dir == Movement::Left), "{:?}", moves);
+        assert!(strategy::by_name("exec:", Budget::Nodes(10), settings.timeout).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use crate::analysis::Grid;
+use crate::requests::{Board, Point, Turn};
+use crate::responses::Movement;
+use crate::safety;
+use crate::search;
+use crate::strategy::Strategy;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+// Simple snakes to spar with. None of them look further ahead than their next move,
+// and all of them avoid certain death when they can.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Bot {
+    // Any move that doesn't die.
+    RandomSafe,
+    // Heads for the nearest food.
+    Greedy,
+    // Follows its own tail around.
+    TailChaser,
+    // Keeps as close to walls and bodies as it can.
+    WallHugger,
+    // Goes for the head of the nearest shorter snake, or food when there isn't one.
+    Hunter,
+    // Moves wherever there's the most room.
+    FloodFill,
+}
+
+impl Bot {
+    pub const ALL: [Bot; 6] = [
+        Bot::RandomSafe,
+        Bot::Greedy,
+        Bot::TailChaser,
+        Bot::WallHugger,
+        Bot::Hunter,
+        Bot::FloodFill,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Bot::RandomSafe => "random-safe",
+            Bot::Greedy => "greedy",
+            Bot::TailChaser => "tail-chaser",
+            Bot::WallHugger => "wall-hugger",
+            Bot::Hunter => "hunter",
+            Bot::FloodFill => "flood-fill",
+        }
+    }
+
+    // The move snake `me` would make on this board.
+    pub fn choose<R: Rng>(self, board: &Board, me: usize, rng: &mut R) -> Movement {
+        let grid = Grid::new(board);
+        let us = &board.snakes[me];
+        match self {
+            Bot::RandomSafe => best(board, me, rng, |_| 0),
+            Bot::Greedy => best(board, me, rng, |next| Reverse(nearest(&grid, next, &board.food))),
+            Bot::TailChaser => best(board, me, rng, |next| Reverse(nearest(&grid, next, &us.body[us.body.len() - 1..]))),
+            Bot::WallHugger => best(board, me, rng, |next| {
+                let blocked = Movement::ALL
+                    .iter()
+                    .filter(|dir| !grid.is_free(next + dir.to_offset()))
+                    .count();
+                (grid.reachable(next) as usize >= us.body.len(), blocked)
+            }),
+            Bot::Hunter => {
+                let prey: Vec<Point> = board
+                    .snakes
+                    .iter()
+                    .filter(|snake| snake.body.len() < us.body.len())
+                    .map(|snake| snake.body[0])
+                    .collect();
+                if prey.is_empty() {
+                    Bot::Greedy.choose(board, me, rng)
+                } else {
+                    best(board, me, rng, |next| {
+                        Reverse(prey.iter().map(|&head| next.distance(head)).min().unwrap())
+                    })
+                }
+            }
+            Bot::FloodFill => best(board, me, rng, |next| grid.reachable(next)),
+        }
+    }
+}
+
+impl std::str::FromStr for Bot {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, String> {
+        Bot::ALL
+            .iter()
+            .cloned()
+            .find(|bot| bot.name() == name)
+            .ok_or_else(|| format!("unknown bot {}", name))
+    }
+}
+
+impl Strategy for Bot {
+    fn choose_move(&mut self, turn: &Turn) -> Movement {
+        match turn.board.snakes.iter().position(|snake| snake.id == turn.you.id) {
+            Some(me) => self.choose(&turn.board, me, &mut search::seeded(turn)),
+            None => Movement::Right,
+        }
+    }
+}
+
+// Moves to get from start to the closest of targets, u32::MAX if none can be reached.
+fn nearest(grid: &Grid, start: Point, targets: &[Point]) -> u32 {
+    let dist = grid.distances(start);
+    targets
+        .iter()
+        .filter_map(|&target| {
+            if target == start {
+                return Some(0);
+            }
+            grid.index(target).and_then(|idx| dist[idx])
+        })
+        .min()
+        .unwrap_or(u32::MAX)
+}
+
+// The safest move, then the one the bot likes best, ties broken at random.
+fn best<R: Rng, K: Ord, F: Fn(Point) -> K>(board: &Board, me: usize, rng: &mut R, key: F) -> Movement {
+    let safety = safety::assess(board, me);
+    let head = board.snakes[me].body[0];
+    let mut dirs = Movement::ALL;
+    dirs.shuffle(rng);
+    dirs.iter()
+        .cloned()
+        .max_by_key(|dir| (safety[dir.to_int()], key(head + dir.to_offset())))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake};
+
+    #[test]
+    fn bots_have_their_habits() {
+        let mut rng = rand::thread_rng();
+        let hungry = board(7, 7, vec![snake("us", 90, &[(3, 3), (3, 4), (3, 5)])], &[(0, 3)]);
+        assert_eq!(Bot::Greedy.choose(&hungry, 0, &mut rng), Movement::Left);
+        assert_eq!(Bot::Hunter.choose(&hungry, 0, &mut rng), Movement::Left);
+
+        let corner = board(7, 7, vec![snake("us", 90, &[(0, 1), (1, 1), (2, 1)])], &[]);
+        assert_eq!(Bot::WallHugger.choose(&corner, 0, &mut rng), Movement::Up);
+
+        // Row 3 is walled off (fed tails stay put), and there's more room below it.
+        let mut split = board(
+            7,
+            7,
+            vec![
+                snake("us", 90, &[(3, 3), (4, 3), (5, 3), (6, 3), (6, 3)]),
+                snake("them", 90, &[(2, 3), (1, 3), (0, 3), (0, 3)]),
+            ],
+            &[],
+        );
+        split.height = 8;
+        assert_eq!(Bot::FloodFill.choose(&split, 0, &mut rng), Movement::Down);
+
+        let curled = board(7, 7, vec![snake("us", 90, &[(3, 3), (3, 4), (4, 4), (5, 4), (5, 3)])], &[]);
+        assert_eq!(Bot::TailChaser.choose(&curled, 0, &mut rng), Movement::Right);
+    }
+
+    #[test]
+    fn hunters_chase_shorter_snakes() {
+        let mut rng = rand::thread_rng();
+        let board = board(
+            7,
+            7,
+            vec![
+                snake("us", 90, &[(3, 3), (3, 4), (3, 5), (3, 6)]),
+                snake("them", 90, &[(6, 3), (6, 4)]),
+            ],
+            &[(0, 3)],
+        );
+        assert_eq!(Bot::Hunter.choose(&board, 0, &mut rng), Movement::Right);
+        assert_eq!(Bot::Greedy.choose(&board, 0, &mut rng), Movement::Left);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for &bot in Bot::ALL.iter() {
+            assert_eq!(bot.name().parse::<Bot>(), Ok(bot));
+            assert_eq!(serde_json::to_string(&bot).unwrap(), format!("\"{}\"", bot.name()));
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use crate::bots::Bot;
+use crate::logging::{self, Format};
+use crate::responses::{HeadType, TailType};
+use arc_swap::ArcSwap;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// Where to look for the config file, unless SNEK_CONFIG says otherwise.
+const DEFAULT_PATH: &str = "snek.json";
+
+// A config that can be swapped for a new one while it's being read, see reload.rs.
+pub type Shared = Arc<ArcSwap<Config>>;
+
+lazy_static! {
+    pub static ref CONFIG: Shared = shared(Config::load());
+}
+
+pub fn shared(config: Config) -> Shared {
+    Arc::new(ArcSwap::from_pointee(config))
+}
+
+// The server's config as it is right now. Hang on to it for as long as things should agree,
+// like a whole move, rather than asking again.
+pub fn current() -> Arc<Config> {
+    CONFIG.load_full()
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub aggression: Aggression,
+    pub endgame: Endgame,
+    pub logging: Logging,
+    pub recording: Recording,
+    pub reload: Reload,
+    pub search: Search,
+    // Play the bots at /bots/<name> as well as our snakes, for arena games over HTTP.
+    pub serve_bots: bool,
+    pub shouts: Shouts,
+    pub snakes: Vec<Mount>,
+    pub weights: Weights,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Aggression {
+    // How much longer than an opponent we have to be before we go after it.
+    pub min_length_lead: usize,
+    // Hungry snakes shouldn't go hunting.
+    pub min_health: i32,
+}
+
+impl Default for Aggression {
+    fn default() -> Self {
+        Aggression {
+            min_length_lead: 1,
+            min_health: 40,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Endgame {
+    // Sealed regions up to this many cells get solved exactly.
+    pub max_region: u32,
+    // Positions the solver may look at per move before it settles for what it has.
+    pub node_limit: u32,
+}
+
+impl Default for Endgame {
+    fn default() -> Self {
+        Endgame {
+            max_region: 24,
+            node_limit: 200_000,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Logging {
+    pub format: Format,
+    // Appended to, or stdout if set to null.
+    pub path: Option<String>,
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Logging {
+            format: Format::default(),
+            path: Some("snek.log".to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Recording {
+    pub enabled: bool,
+    // One JSONL file per game goes in here.
+    pub dir: String,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Recording {
+            enabled: false,
+            dir: "games".to_string(),
+        }
+    }
+}
+
+// How the running server picks up a changed config.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Reload {
+    // How often to check the config file for changes, or never for 0.
+    pub watch_millis: u64,
+    // For POST /admin/reload, as `Authorization: Bearer <token>`. Nobody gets in without one.
+    pub token: Option<String>,
+}
+
+impl Default for Reload {
+    fn default() -> Self {
+        Reload {
+            watch_millis: 1000,
+            token: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Search {
+    // Which bot to expect opponents to play like, or random moves if none.
+    pub opponent_model: Option<Bot>,
+    // Instead of racing the clock with worker threads, look at exactly this many futures
+    // on one thread with seeded randomness, so every move can be reproduced.
+    pub nodes: Option<u32>,
+    // How long the server's workers get to think about each move.
+    pub think_millis: u64,
+    // Threads searching each game on the server.
+    pub workers: u32,
+    // How many turns each trip into the future goes before it's scored.
+    pub explore_depth: u32,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Search {
+            opponent_model: None,
+            nodes: None,
+            think_millis: 450,
+            workers: 2,
+            explore_depth: 5,
+        }
+    }
+}
+
+// What we shout with each move, see shout.rs. Each event has a few templates, taken in turn,
+// and an event with none falls through to the next one down.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Shouts {
+    // Every move we can make is death.
+    pub doom: Vec<String>,
+    // An opponent died since our last move.
+    pub eliminated: Vec<String>,
+    // We're moving where a shorter snake's head can meet ours.
+    pub head_to_head: Vec<String>,
+    // Our health is at or below low_health.
+    pub hungry: Vec<String>,
+    pub otherwise: Vec<String>,
+    pub low_health: i32,
+}
+
+impl Default for Shouts {
+    fn default() -> Self {
+        let templates = |texts: &[&str]| texts.iter().map(|text| text.to_string()).collect();
+        Shouts {
+            doom: templates(&["You've met with a terrible fate, haven't you?"]),
+            eliminated: templates(&["One down, {opponents} to go.", "And then there were {snakes}."]),
+            head_to_head: templates(&["Going {move}. Try and stop me."]),
+            hungry: templates(&["{health} health and not a crumb in sight."]),
+            otherwise: templates(&["{stats}"]),
+            low_health: 20,
+        }
+    }
+}
+
+// How much each part of the evaluation counts, in percent.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Weights {
+    // Staying near the middle of the board.
+    pub centre: u32,
+    pub health: u32,
+    // Room to move, see analysis::space_score.
+    pub space: u32,
+    // Bullying shorter snakes, see aggression::hunt_score.
+    pub hunt: u32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            centre: 100,
+            health: 100,
+            space: 100,
+            hunt: 100,
+        }
+    }
+}
+
+// A snake the server plays, with /start, /move and /end under `path`.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct Mount {
+    pub path: String,
+    // As for the arena: tree, tree:<config.json>, safe or a bot.
+    pub strategy: String,
+    pub color: String,
+    pub head: HeadType,
+    pub tail: TailType,
+}
+
+impl Default for Mount {
+    fn default() -> Self {
+        Mount {
+            path: "/".to_string(),
+            strategy: "tree".to_string(),
+            color: "#FF0080".to_string(),
+            head: HeadType::Safe, // We're Rust, after all
+            tail: TailType::BlockBum, // because we block a LOT.
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            aggression: Aggression::default(),
+            endgame: Endgame::default(),
+            logging: Logging::default(),
+            recording: Recording::default(),
+            reload: Reload::default(),
+            search: Search::default(),
+            serve_bots: false,
+            shouts: Shouts::default(),
+            snakes: vec![Mount::default()],
+            weights: Weights::default(),
+        }
+    }
+}
+
+impl Config {
+    // Where the config file is, whether or not there is one.
+    pub fn path() -> String {
+        std::env::var("SNEK_CONFIG").unwrap_or_else(|_| DEFAULT_PATH.to_string())
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path();
+        if !std::path::Path::new(&path).exists() {
+            return Config::default();
+        }
+        Self::from_file(&path).unwrap_or_else(|e| {
+            let config = Config::default();
+            // The server's config is still being loaded, so this can't wait for it.
+            logging::warn("config not loaded, using the defaults").field("error", e).emit_to(&config.logging);
+            config
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Can't read config {}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("Bad config {}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let config = Config::parse(r#"{"aggression": {"min_health": 70}}"#).unwrap();
+        assert_eq!(config.aggression.min_health, 70);
+        assert_eq!(config.aggression.min_length_lead, 1);
+        assert_eq!(Config::parse("{}").unwrap(), Config::default());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::requests::{Board, Point};
+use crate::responses::Movement;
+use crate::search::SnekStep;
+use std::fmt::Write;
+use std::sync::Arc;
+
+// Search trees as Graphviz DOT, for post-mortems: `dot -Tsvg tree.dot > tree.svg`.
+// Each node shows the moves that led to it, its score and visits, and the board.
+
+// How far down to draw, unless told otherwise.
+pub const DEFAULT_DEPTH: u32 = 3;
+
+// Snakes are lettered in the order they're on the root board, since ids are long
+// and dead snakes drop out of the boards further down.
+fn letter(ids: &[String], id: &str) -> char {
+    let idx = ids.iter().position(|known| known == id).unwrap_or(25).min(25);
+    (b'a' + idx as u8) as char
+}
+
+// The board as text: heads in capitals, bodies in lower case, food as *.
+pub fn ascii(board: &Board, ids: &[String]) -> Vec<String> {
+    let mut rows = vec![vec!['.'; board.width.max(0) as usize]; board.height.max(0) as usize];
+    let mut put = |point: Point, c: char| {
+        if point.x >= 0 && point.y >= 0 && point.x < board.width && point.y < board.height {
+            rows[point.y as usize][point.x as usize] = c;
+        }
+    };
+    for &food in board.food.iter() {
+        put(food, '*');
+    }
+    for snake in board.snakes.iter() {
+        let c = letter(ids, &snake.id);
+        // Tail first, so the head goes on top of a body curled up under it.
+        for (idx, &piece) in snake.body.iter().enumerate().rev() {
+            put(piece, if idx == 0 { c.to_ascii_uppercase() } else { c });
+        }
+    }
+    rows.into_iter().map(|row| row.into_iter().collect()).collect()
+}
+
+// What every snake did to get from one board to the next, like "a:up b:left c:dead".
+pub fn joint_move(before: &Board, after: &Board, ids: &[String]) -> String {
+    let moves: Vec<String> = before
+        .snakes
+        .iter()
+        .map(|snake| {
+            let moved = after.snakes.iter().find(|next| next.id == snake.id).and_then(|next| {
+                Movement::ALL
+                    .iter()
+                    .find(|dir| snake.body[0] + dir.to_offset() == next.body[0])
+                    .map(|dir| dir.to_string())
+            });
+            format!("{}:{}", letter(ids, &snake.id), moved.unwrap_or_else(|| "dead".to_string()))
+        })
+        .collect();
+    moves.join(" ")
+}
+
+// Double quotes and backslashes are all DOT cares about in a quoted string.
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Lines of a label, each left justified.
+fn label(lines: &[String]) -> String {
+    let escaped: Vec<String> = lines.iter().map(|line| line.replace('\\', "\\\\").replace('"', "\\\"")).collect();
+    format!("\"{}\\l\"", escaped.join("\\l"))
+}
+
+// `root` and everything under it down to `depth` moves.
+pub fn render(name: &str, root: &Arc<SnekStep>, depth: u32) -> String {
+    let ids: Vec<String> = root.board().snakes.iter().map(|snake| snake.id.clone()).collect();
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph {} {{", quoted(name));
+    let _ = writeln!(out, "    node [shape=box, fontname=\"monospace\"];");
+    let mut next_id = 0;
+    // (node id, step, how it was reached, moves from the root)
+    let mut todo = vec![(0, root.clone(), "root".to_string(), 0)];
+    while let Some((id, step, reached, moves)) = todo.pop() {
+        let mut lines = vec![reached, format!("score {}  visits {}", step.score(), step.visits())];
+        lines.extend(ascii(step.board(), &ids));
+        let _ = writeln!(out, "    n{} [label={}];", id, label(&lines));
+        if moves >= depth {
+            continue;
+        }
+        // Pushed backwards, so children come out in Movement::ALL order.
+        for (dir, child) in step.expanded().into_iter().rev() {
+            next_id += 1;
+            let _ = writeln!(out, "    n{} -> n{} [label={}];", id, next_id, quoted(&dir.to_string()));
+            let reached = joint_move(step.board(), child.board(), &ids);
+            todo.push((next_id, child, reached, moves + 1));
+        }
+    }
+    out + "}\n"
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake, turn};
+    use crate::config::Config;
+    use crate::requests::Turn;
+    use crate::search::{self, Budget};
+
+    fn tiny() -> Turn {
+        let snakes = vec![snake("us", 90, &[(1, 1), (1, 2), (1, 3)]), snake("them", 90, &[(3, 3), (2, 3)])];
+        turn("dot", 2, board(4, 4, snakes, &[(3, 0)]))
+    }
+
+    #[test]
+    fn draws_boards() {
+        let turn = tiny();
+        let ids = vec!["us".to_string(), "them".to_string()];
+        assert_eq!(ascii(&turn.board, &ids), vec!["...*", ".A..", ".a..", ".abB"]);
+    }
+
+    #[test]
+    fn renders_to_depth() {
+        let turn = tiny();
+        let tree = search::grow(&turn, Budget::Depth(2), &Config::default(), &mut search::seeded(&turn));
+        let shallow = render("us", &tree, 1);
+        assert!(shallow.starts_with("digraph \"us\" {\n"));
+        assert!(shallow.ends_with("}\n"));
+        let edges = shallow.lines().filter(|line| line.contains("->")).count();
+        assert_eq!(edges, tree.expanded().len());
+        assert!(shallow.contains("[label=\"root\\lscore "));
+        assert!(shallow.contains("\\l.A..\\l"));
+        assert!(shallow.contains("a:up b:"));
+        let deep = render("us", &tree, 2);
+        assert!(deep.lines().filter(|line| line.contains("->")).count() > edges);
+    }
+}
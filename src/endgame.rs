@@ -0,0 +1,162 @@
+use crate::analysis::Grid;
+use crate::requests::{Board, Point};
+use crate::responses::Movement;
+use std::collections::VecDeque;
+
+// Size of the region our head is stuck in, if no other snake can get into it.
+pub fn sealed_region(board: &Board, me: usize) -> Option<u32> {
+    let grid = Grid::new(board);
+    let dist = grid.distances(board.snakes[me].body[0]);
+    for (idx, snake) in board.snakes.iter().enumerate() {
+        if idx == me {
+            continue;
+        }
+        let head = match grid.index(snake.body[0]) {
+            Some(head) => head,
+            None => continue,
+        };
+        if grid.neighbours(head).any(|next| dist[next].is_some()) {
+            return None;
+        }
+    }
+    Some(dist.iter().filter(|d| d.is_some()).count() as u32 - 1)
+}
+
+// Our snake with the board after one move, the same way thread_work simulates it.
+pub fn advance(board: &Board, me: usize, dir: Movement) -> Board {
+    let mut board = board.clone();
+    let snake = &mut board.snakes[me];
+    if snake.health != 100 {
+        snake.body.pop();
+    }
+    let head = snake.body[0] + dir.to_offset();
+    snake.body.insert(0, head);
+    snake.health -= 1;
+    if let Some(food) = board.food.iter().position(|&food| food == head) {
+        board.food.remove(food);
+        snake.health = 100;
+    }
+    board
+}
+
+#[derive(Clone)]
+struct State {
+    body: VecDeque<Point>,
+    health: i32,
+    food: Vec<Point>,
+}
+
+struct Solver {
+    // Everything that isn't us, assumed to stay put.
+    walls: Grid,
+    horizon: u32,
+    nodes: u32,
+    node_limit: u32,
+}
+
+impl Solver {
+    fn step(&self, state: &State, dir: Movement) -> Option<State> {
+        let mut next = state.clone();
+        if next.health != 100 {
+            next.body.pop_back();
+        }
+        let head = next.body[0] + dir.to_offset();
+        if !self.walls.is_free(head) || next.body.contains(&head) {
+            return None;
+        }
+        next.body.push_front(head);
+        next.health -= 1;
+        if next.health <= 0 {
+            return None;
+        }
+        if let Some(food) = next.food.iter().position(|&food| food == head) {
+            next.food.remove(food);
+            next.health = 100;
+        }
+        Some(next)
+    }
+
+    // Most turns we can still survive from here.
+    fn longest(&mut self, state: &State, depth: u32) -> u32 {
+        if depth >= self.horizon || self.nodes >= self.node_limit {
+            return 0;
+        }
+        self.nodes += 1;
+        let mut best = 0;
+        for &dir in Movement::ALL.iter() {
+            if let Some(next) = self.step(state, dir) {
+                best = best.max(1 + self.longest(&next, depth + 1));
+                if depth + best >= self.horizon {
+                    break;
+                }
+            }
+        }
+        best
+    }
+}
+
+// Turns survived after each first move (indexed by Movement::to_int), None for instant death.
+// Our tail moves out of the way as we go; other snakes are treated as walls.
+// Lasting as long as the region plus our length counts as forever, we can chase our tail from there.
+pub fn solve(board: &Board, me: usize, node_limit: u32) -> [Option<u32>; 4] {
+    let us = &board.snakes[me];
+    let mut others = board.clone();
+    others.snakes.remove(me);
+    let mut solver = Solver {
+        walls: Grid::new(&others),
+        horizon: Grid::new(board).reachable(us.body[0]) + us.body.len() as u32 + 1,
+        nodes: 0,
+        node_limit: 0,
+    };
+    let state = State {
+        body: us.body.iter().cloned().collect(),
+        health: us.health,
+        food: board.food.clone(),
+    };
+
+    let mut turns = [None; 4];
+    for &dir in Movement::ALL.iter() {
+        if let Some(next) = solver.step(&state, dir) {
+            // Each first move gets its share of the budget.
+            solver.node_limit += node_limit / Movement::ALL.len() as u32;
+            turns[dir.to_int()] = Some(1 + solver.longest(&next, 1));
+        }
+    }
+    turns
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake};
+
+    #[test]
+    fn dead_end_counts_tail() {
+        // . H # # .
+        let board = board(5, 1, vec![snake("us", 90, &[(1, 0), (2, 0), (3, 0)])], &[]);
+        assert_eq!(sealed_region(&board, 0), Some(1));
+        // Left gets one turn before we run into ourselves, the cell our tail leaves is out of reach.
+        assert_eq!(solve(&board, 0, 1000), [None, Some(1), None, None]);
+    }
+
+    #[test]
+    fn chases_tail_forever() {
+        // H # .
+        // . # .
+        let board = board(3, 2, vec![snake("us", 90, &[(0, 0), (1, 0), (1, 1)])], &[]);
+        let region = sealed_region(&board, 0).unwrap();
+        let turns = solve(&board, 0, 1000);
+        assert_eq!(turns[Movement::Down.to_int()], Some(region + 3 + 1));
+    }
+
+    #[test]
+    fn not_sealed_with_company() {
+        let board = board(
+            5,
+            3,
+            vec![snake("us", 90, &[(0, 0), (0, 1)]), snake("them", 90, &[(4, 2), (4, 1)])],
+            &[],
+        );
+        assert_eq!(sealed_region(&board, 0), None);
+    }
+}
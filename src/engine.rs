@@ -0,0 +1,49 @@
+use crate::config::Config;
+use crate::requests::Turn;
+use crate::search::{self, Budget, Decision};
+use std::time::Instant;
+
+// The search on its own, for anything that wants a move without running the server.
+pub struct Engine {
+    pub config: Config,
+}
+
+impl Engine {
+    pub fn new(config: Config) -> Self {
+        Engine { config }
+    }
+
+    // The best move found by the deadline, thinking on this thread.
+    pub fn think(&self, turn: &Turn, deadline: Instant) -> Decision {
+        let time = deadline.saturating_duration_since(Instant::now());
+        self.analyse(turn, Budget::Time(time))
+    }
+
+    // Same again with any budget. Node and depth budgets always give the same answer.
+    pub fn analyse(&self, turn: &Turn, budget: Budget) -> Decision {
+        search::think(turn, budget, &self.config, &mut search::seeded(turn))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake, turn};
+    use crate::responses::Movement;
+    use std::time::Duration;
+
+    fn duel() -> Turn {
+        let snakes = vec![snake("us", 90, &[(0, 3), (1, 3), (2, 3)]), snake("them", 90, &[(5, 5), (5, 6), (6, 6)])];
+        turn("game-id-string", 2, board(7, 7, snakes, &[]))
+    }
+
+    #[test]
+    fn thinks_until_the_deadline() {
+        let engine = Engine::new(Config::default());
+        let began = Instant::now();
+        let decision = engine.think(&duel(), began + Duration::from_millis(50));
+        assert!(began.elapsed() < Duration::from_millis(500));
+        // Left is the wall, Right our neck.
+        assert!(decision.movement == Movement::Up || decision.movement == Movement::Down);
+    }
+}
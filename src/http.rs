@@ -0,0 +1,177 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+// Just enough of an HTTP client to talk to snakes on the local network.
+// Only plain http, one request per connection.
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "timed out")
+}
+
+// "http://host:port/path" into ("host:port", "/path")
+fn split_url(url: &str) -> io::Result<(&str, &str)> {
+    if !url.starts_with("http://") {
+        return Err(invalid(format!("only http:// urls are supported, not {}", url)));
+    }
+    let rest = &url["http://".len()..];
+    Ok(match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    })
+}
+
+fn dechunk(mut body: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|pair| pair == b"\r\n")
+            .ok_or_else(|| invalid("truncated chunk".to_string()))?;
+        let size = std::str::from_utf8(&body[..line_end])
+            .ok()
+            .and_then(|line| usize::from_str_radix(line.split(';').next().unwrap().trim(), 16).ok())
+            .ok_or_else(|| invalid("bad chunk size".to_string()))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(out);
+        }
+        if body.len() < size {
+            return Err(invalid("truncated chunk".to_string()));
+        }
+        out.extend_from_slice(&body[..size]);
+        body = &body[(size + 2).min(body.len())..];
+    }
+}
+
+fn header_end(response: &[u8]) -> Option<usize> {
+    response.windows(4).position(|window| window == b"\r\n\r\n")
+}
+
+// Whether a response with a Content-Length has all arrived.
+fn complete(response: &[u8]) -> bool {
+    let split = match header_end(response) {
+        Some(split) => split,
+        None => return false,
+    };
+    let head = String::from_utf8_lossy(&response[..split]).to_lowercase();
+    let len = head
+        .lines()
+        .filter(|line| line.starts_with("content-length:"))
+        .filter_map(|line| line["content-length:".len()..].trim().parse::<usize>().ok())
+        .next();
+    match len {
+        Some(len) => response.len() >= split + 4 + len,
+        None => false,
+    }
+}
+
+// POST some JSON and return the body of a 2xx response, all within `timeout`.
+pub fn post(url: &str, json: &str, timeout: Duration) -> io::Result<String> {
+    let deadline = Instant::now() + timeout;
+    let left = || deadline.checked_duration_since(Instant::now()).filter(|left| *left > Duration::from_millis(0));
+
+    let (host, path) = split_url(url)?;
+    let addr = host
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| invalid(format!("can't resolve {}", host)))?;
+    let mut stream = TcpStream::connect_timeout(&addr, left().ok_or_else(timed_out)?)?;
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(left())?;
+    // In one write, so it goes out in one piece to servers that only read once.
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        json.len(),
+        json
+    );
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = vec![];
+    let mut buf = [0; 4096];
+    loop {
+        stream.set_read_timeout(Some(left().ok_or_else(timed_out)?))?;
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                response.extend_from_slice(&buf[..n]);
+                // Don't wait on servers that keep the connection open anyway.
+                if complete(&response) {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Err(timed_out()),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let split = header_end(&response)
+        .ok_or_else(|| invalid("no end to the headers".to_string()))?;
+    let head = String::from_utf8_lossy(&response[..split]).to_lowercase();
+    let status = head.split_whitespace().nth(1).unwrap_or("");
+    if !status.starts_with('2') {
+        return Err(invalid(format!("{} answered {}", url, status)));
+    }
+    let body = &response[split + 4..];
+    let body = if head.contains("transfer-encoding: chunked") { dechunk(body)? } else { body.to_vec() };
+    String::from_utf8(body).map_err(|e| invalid(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+
+    // Answer one request with `response` after `delay`, and hand back what was asked.
+    fn serve(response: &'static str, delay: Duration) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/snake/move", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let n = stream.read(&mut request).unwrap();
+            std::thread::sleep(delay);
+            let _ = stream.write_all(response.as_bytes());
+            String::from_utf8_lossy(&request[..n]).to_string()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn posts_json() {
+        let (url, server) = serve(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 15\r\n\r\n{\"move\":\"left\"}",
+            Duration::from_millis(0),
+        );
+        let body = post(&url, "{\"turn\":3}", Duration::from_millis(1000)).unwrap();
+        assert_eq!(body, "{\"move\":\"left\"}");
+        // The server only reads once, and still gets all of it.
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /snake/move HTTP/1.1\r\n"));
+        assert!(request.ends_with("\r\n\r\n{\"turn\":3}"));
+    }
+
+    #[test]
+    fn reads_chunks() {
+        let (url, _) = serve(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n",
+            Duration::from_millis(0),
+        );
+        assert_eq!(post(&url, "{}", Duration::from_millis(1000)).unwrap(), "abcde");
+    }
+
+    #[test]
+    fn gives_up_in_time() {
+        let (url, _) = serve("HTTP/1.1 200 OK\r\n\r\n", Duration::from_millis(500));
+        let began = Instant::now();
+        let e = post(&url, "{}", Duration::from_millis(100)).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert!(began.elapsed() < Duration::from_millis(400));
+    }
+}
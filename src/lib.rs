@@ -0,0 +1,34 @@
+// Everything but the web server: the board model, the rules, the evaluators, the search
+// and the tools built on them.
+
+pub mod aggression;
+pub mod analysis;
+pub mod arena;
+pub mod bots;
+pub mod config;
+pub mod dot;
+pub mod endgame;
+pub mod engine;
+pub mod http;
+pub mod logging;
+pub mod metrics;
+pub mod protocol;
+pub mod ratings;
+pub mod recorder;
+pub mod reload;
+pub mod replay;
+pub mod requests;
+pub mod responses;
+pub mod rules;
+pub mod safety;
+pub mod search;
+pub mod shout;
+pub mod snakes;
+pub mod solo;
+pub mod strategy;
+pub mod terminal;
+pub mod tune;
+
+pub use engine::Engine;
+pub use requests::{Board, Point, Snake, Turn};
+pub use search::{Budget, Decision};
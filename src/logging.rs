@@ -0,0 +1,198 @@
+use crate::config::{self, Logging};
+use crate::requests::Turn;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Structured logs: a message plus named fields, one event per line.
+// Events about a turn carry its game, turn and snake, so one game can be grepped out of a busy server.
+//
+//   logging::info("move").turn(&turn).field("move", movement).emit();
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    // time LEVEL message key=value ...
+    Pretty,
+    // {"time":...,"level":...,"message":...,"key":value,...}
+    Json,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Pretty
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Level {
+    Info,
+    Warn,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+        }
+    }
+}
+
+pub struct Event {
+    level: Level,
+    message: String,
+    fields: Vec<(&'static str, Value)>,
+}
+
+pub fn info(message: &str) -> Event {
+    Event {
+        level: Level::Info,
+        message: message.to_string(),
+        fields: vec![],
+    }
+}
+
+pub fn warn(message: &str) -> Event {
+    Event {
+        level: Level::Warn,
+        message: message.to_string(),
+        fields: vec![],
+    }
+}
+
+enum Sink {
+    File(File),
+    // Through println!, so tests capture it.
+    Stdout,
+    // When the file can't be opened.
+    Stderr,
+}
+
+lazy_static! {
+    // Opened by the first event, as the config says then.
+    static ref SINK: Mutex<Option<Sink>> = Mutex::new(None);
+}
+
+fn open(logging: &Logging) -> Sink {
+    let path = match logging.path {
+        Some(ref path) => path,
+        None => return Sink::Stdout,
+    };
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => Sink::File(file),
+        Err(e) => {
+            let complaint = warn("can't open the log file, logging to stderr").field("path", path).field("error", e.to_string());
+            eprintln!("{}", complaint.line(logging.format));
+            Sink::Stderr
+        }
+    }
+}
+
+// Strings that need no quoting go in as they are.
+fn pretty_value(value: &Value) -> String {
+    match value {
+        Value::String(text) if !text.is_empty() && !text.contains(|c: char| c.is_whitespace() || c == '"') => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+impl Event {
+    // Which game, turn and snake this is about.
+    pub fn turn(self, turn: &Turn) -> Self {
+        self.field("game", &turn.game.id).field("turn", turn.turn).field("snake", &turn.you.id)
+    }
+
+    pub fn field<T: Serialize>(mut self, name: &'static str, value: T) -> Self {
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        self.fields.push((name, value));
+        self
+    }
+
+    pub fn json(&self, time: f64) -> String {
+        let mut line = format!(
+            "{{\"time\":{:.3},\"level\":\"{}\",\"message\":{}",
+            time,
+            self.level.name(),
+            Value::String(self.message.clone())
+        );
+        for (name, value) in self.fields.iter() {
+            line += &format!(",\"{}\":{}", name, value);
+        }
+        line + "}"
+    }
+
+    pub fn pretty(&self, time: f64) -> String {
+        let mut line = format!("{:.3} {:<5} {}", time, self.level.name().to_uppercase(), self.message);
+        for (name, value) in self.fields.iter() {
+            line += &format!(" {}={}", name, pretty_value(value));
+        }
+        line
+    }
+
+    fn line(&self, format: Format) -> String {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0);
+        match format {
+            Format::Pretty => self.pretty(time),
+            Format::Json => self.json(time),
+        }
+    }
+
+    pub fn emit(self) {
+        self.emit_to(&config::current().logging)
+    }
+
+    // For when there's no server config to ask yet, like while it's being loaded.
+    pub fn emit_to(self, logging: &Logging) {
+        let line = self.line(logging.format);
+        let mut sink = SINK.lock().unwrap_or_else(|e| e.into_inner());
+        match *sink.get_or_insert_with(|| open(logging)) {
+            Sink::File(ref mut file) => {
+                // Nowhere left to complain to.
+                let _ = writeln!(file, "{}", line);
+            }
+            Sink::Stdout => println!("{}", line),
+            Sink::Stderr => eprintln!("{}", line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::responses::Movement;
+
+    fn event() -> Event {
+        warn("late move")
+            .field("game", "abc-123")
+            .field("turn", 7)
+            .field("move", Movement::Up)
+            .field("scores", [Some(3), None])
+            .field("url", "http://somewhere else")
+    }
+
+    #[test]
+    fn formats() {
+        let event = event();
+        assert_eq!(
+            event.pretty(12.5),
+            "12.500 WARN  late move game=abc-123 turn=7 move=up scores=[3,null] url=\"http://somewhere else\""
+        );
+        let json: Value = serde_json::from_str(&event.json(12.5)).unwrap();
+        assert_eq!(json["level"], "warn");
+        assert_eq!(json["message"], "late move");
+        assert_eq!(json["turn"], 7);
+        assert_eq!(json["scores"][1], Value::Null);
+    }
+
+    #[test]
+    fn parses_config() {
+        let logging: Logging = serde_json::from_str(r#"{"format": "json", "path": "snek.log"}"#).unwrap();
+        assert_eq!(logging.format, Format::Json);
+        assert_eq!(logging.path, Some("snek.log".to_string()));
+    }
+}
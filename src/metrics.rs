@@ -0,0 +1,236 @@
+use crate::recorder::Stats;
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Counters and histograms for /metrics, in Prometheus' text format.
+
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.05, 0.1, 0.2, 0.3, 0.4, 0.45, 0.5, 0.75, 1.0];
+const NODES_BUCKETS: &[f64] = &[100.0, 1_000.0, 5_000.0, 10_000.0, 50_000.0, 100_000.0, 500_000.0, 1_000_000.0];
+const DEPTH_BUCKETS: &[f64] = &[1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0];
+
+pub struct Histogram {
+    bounds: &'static [f64],
+    // How many observations fell in each bucket, with everything too big for the last one at the end.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let bucket = self.bounds.iter().position(|&bound| value <= bound).unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut below = 0;
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter()) {
+            below += count;
+            let le = format!("le=\"{}\"", bound);
+            let _ = writeln!(out, "{}_bucket{} {}", name, braced(&[labels, &le]), below);
+        }
+        let _ = writeln!(out, "{}_bucket{} {}", name, braced(&[labels, "le=\"+Inf\""]), self.count());
+        let _ = writeln!(out, "{}_sum{} {}", name, braced(&[labels]), self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braced(&[labels]), self.count());
+    }
+}
+
+// {a="b",c="d"} for whichever labels there are, nothing for none.
+fn braced(labels: &[&str]) -> String {
+    let labels: Vec<&str> = labels.iter().cloned().filter(|label| !label.is_empty()).collect();
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+pub struct Metrics {
+    // By endpoint, which also counts the requests.
+    latency: BTreeMap<&'static str, Histogram>,
+    nodes: Histogram,
+    depth: Histogram,
+    max_depth: u32,
+    fallbacks: u64,
+    panics: u64,
+    active_games: usize,
+    workers: usize,
+}
+
+lazy_static! {
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics {
+        latency: BTreeMap::new(),
+        nodes: Histogram::new(NODES_BUCKETS),
+        depth: Histogram::new(DEPTH_BUCKETS),
+        max_depth: 0,
+        fallbacks: 0,
+        panics: 0,
+        active_games: 0,
+        workers: 0,
+    });
+}
+
+// Whatever panicked while holding the lock, the numbers are still worth having.
+fn metrics() -> std::sync::MutexGuard<'static, Metrics> {
+    METRICS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn request(endpoint: &'static str, took: Duration) {
+    metrics()
+        .latency
+        .entry(endpoint)
+        .or_insert_with(|| Histogram::new(LATENCY_BUCKETS))
+        .observe(took.as_secs_f64());
+}
+
+// Counts a request to `endpoint` when it's dropped, however the handler got out.
+pub struct Timer {
+    endpoint: &'static str,
+    began: Instant,
+}
+
+impl Timer {
+    pub fn new(endpoint: &'static str) -> Self {
+        Timer {
+            endpoint,
+            began: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        request(self.endpoint, self.began.elapsed());
+    }
+}
+
+pub fn movement(stats: &Stats) {
+    let mut metrics = metrics();
+    metrics.nodes.observe(stats.futures as f64);
+    metrics.depth.observe(stats.depth as f64);
+    metrics.max_depth = metrics.max_depth.max(stats.depth);
+    if stats.fallback {
+        metrics.fallbacks += 1;
+    }
+}
+
+pub fn fallback() {
+    metrics().fallbacks += 1;
+}
+
+pub fn panicked() {
+    metrics().panics += 1;
+}
+
+pub fn active_games(games: usize) {
+    metrics().active_games = games;
+}
+
+// One more search worker until it's dropped, even if the worker panics.
+pub struct Working;
+
+impl Working {
+    pub fn start() -> Self {
+        metrics().workers += 1;
+        Working
+    }
+}
+
+impl Drop for Working {
+    fn drop(&mut self) {
+        metrics().workers -= 1;
+    }
+}
+
+pub fn render() -> String {
+    let metrics = metrics();
+    let mut out = String::new();
+    let header = |out: &mut String, name: &str, kind: &str, help: &str| {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+    };
+
+    header(&mut out, "snek_requests_total", "counter", "Requests handled, by endpoint.");
+    for (endpoint, latency) in metrics.latency.iter() {
+        let _ = writeln!(out, "snek_requests_total{{endpoint=\"{}\"}} {}", endpoint, latency.count());
+    }
+    header(&mut out, "snek_response_seconds", "histogram", "Time taken to answer, by endpoint.");
+    for (endpoint, latency) in metrics.latency.iter() {
+        latency.render(&mut out, "snek_response_seconds", &format!("endpoint=\"{}\"", endpoint));
+    }
+    header(&mut out, "snek_move_nodes", "histogram", "Futures looked at for each move.");
+    metrics.nodes.render(&mut out, "snek_move_nodes", "");
+    header(&mut out, "snek_move_depth", "histogram", "Length of the principal variation for each move.");
+    metrics.depth.render(&mut out, "snek_move_depth", "");
+    header(&mut out, "snek_max_depth", "gauge", "Longest principal variation so far.");
+    let _ = writeln!(out, "snek_max_depth {}", metrics.max_depth);
+    header(&mut out, "snek_fallback_moves_total", "counter", "Moves where the search was overruled or had no answer.");
+    let _ = writeln!(out, "snek_fallback_moves_total {}", metrics.fallbacks);
+    header(&mut out, "snek_panics_caught_total", "counter", "Panics caught in handlers and search workers.");
+    let _ = writeln!(out, "snek_panics_caught_total {}", metrics.panics);
+    header(&mut out, "snek_active_games", "gauge", "Games being played right now.");
+    let _ = writeln!(out, "snek_active_games {}", metrics.active_games);
+    header(&mut out, "snek_workers", "gauge", "Search worker threads running.");
+    let _ = writeln!(out, "snek_workers {}", metrics.workers);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buckets_are_cumulative() {
+        let mut histogram = Histogram::new(&[1.0, 10.0]);
+        for &value in [0.5, 1.0, 5.0, 50.0].iter() {
+            histogram.observe(value);
+        }
+        let mut out = String::new();
+        histogram.render(&mut out, "x", "a=\"b\"");
+        assert_eq!(
+            out,
+            "x_bucket{a=\"b\",le=\"1\"} 2\n\
+             x_bucket{a=\"b\",le=\"10\"} 3\n\
+             x_bucket{a=\"b\",le=\"+Inf\"} 4\n\
+             x_sum{a=\"b\"} 56.5\n\
+             x_count{a=\"b\"} 4\n"
+        );
+        let mut out = String::new();
+        histogram.render(&mut out, "x", "");
+        assert!(out.ends_with("x_sum 56.5\nx_count 4\n"));
+    }
+
+    #[test]
+    fn renders_everything() {
+        request("test", Duration::from_millis(20));
+        movement(&Stats {
+            futures: 300,
+            score: 10,
+            scores: [Some(10), None, None, None],
+            depth: 7,
+            fallback: true,
+        });
+        let working = Working::start();
+        let text = render();
+        assert!(text.contains("snek_requests_total{endpoint=\"test\"}"));
+        assert!(text.contains("snek_response_seconds_bucket{endpoint=\"test\",le=\"0.05\"}"));
+        assert!(text.contains("snek_move_nodes_count"));
+        assert!(text.contains("# TYPE snek_workers gauge"));
+        assert!(!text.contains("snek_workers 0\n"));
+        drop(working);
+    }
+}
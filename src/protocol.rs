@@ -0,0 +1,375 @@
+use crate::config::{self, Config, Shared};
+use crate::requests::Turn;
+use crate::responses::Movement;
+use crate::search::{self, Budget, Decision};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Write};
+use std::process::exit;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: rusty_snake engine [--config FILE]";
+
+// How often a search in progress says how it's going.
+const INFO_EVERY: Duration = Duration::from_millis(100);
+
+// What the engine can be told, one per line:
+//   newgame                forget the last game
+//   position <turn json>   the turn to think about, as the game server would send it
+//   go movetime <millis>   think in the background, with info as it goes
+//   go nodes <n>           think about exactly this much, the same answer every time
+//   go depth <n>           every move of every snake n turns deep, also the same every time
+//   go infinite            think until told to stop
+//   stop                   answer now
+//   stats                  totals since the engine started
+//   quit
+#[derive(PartialEq, Debug, Clone)]
+pub enum Command {
+    NewGame,
+    Position(Box<Turn>),
+    // None thinks until stopped.
+    Go(Option<Budget>),
+    Stop,
+    Stats,
+    Quit,
+}
+
+// What the engine says back, one JSON object per line.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Reply {
+    // After newgame, so whoever's driving knows the engine's caught up.
+    Ready,
+    Info {
+        millis: u64,
+        nodes: u32,
+        depth: usize,
+        best: Movement,
+        scores: [Option<u32>; 4],
+        pv: Vec<Movement>,
+    },
+    BestMove {
+        #[serde(rename = "move")]
+        movement: Movement,
+        // The position's turn, so an answer that comes too late can't pass for the next one.
+        turn: u32,
+    },
+    Stats {
+        games: u32,
+        moves: u32,
+        nodes: u64,
+        millis: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+fn number<T: std::str::FromStr>(arg: Option<&str>) -> Result<T, String> {
+    arg.and_then(|arg| arg.parse().ok()).ok_or_else(|| "go needs a number".to_string())
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let mut parts = line.splitn(2, ' ');
+    let command = parts.next().unwrap_or("");
+    let rest = parts.next().unwrap_or("").trim();
+    match command {
+        "newgame" => Ok(Command::NewGame),
+        "position" => serde_json::from_str(rest)
+            .map(|turn| Command::Position(Box::new(turn)))
+            .map_err(|e| format!("bad position: {}", e)),
+        "go" => {
+            let mut args = rest.split_whitespace();
+            let budget = match args.next() {
+                Some("movetime") => Some(Budget::Time(Duration::from_millis(number(args.next())?))),
+                Some("nodes") => Some(Budget::Nodes(number(args.next())?)),
+                Some("depth") => Some(Budget::Depth(number(args.next())?)),
+                Some("infinite") => None,
+                _ => return Err("go movetime|nodes|depth <n>, or go infinite".to_string()),
+            };
+            Ok(Command::Go(budget))
+        }
+        "stop" => Ok(Command::Stop),
+        "stats" => Ok(Command::Stats),
+        "quit" => Ok(Command::Quit),
+        _ => Err(format!("unknown command {:?}", command)),
+    }
+}
+
+// One engine's worth of state, between commands.
+pub struct Session {
+    pub config: Shared,
+    turn: Option<Turn>,
+    games: u32,
+    moves: u32,
+    nodes: u64,
+    millis: u64,
+}
+
+fn send(out: &mut dyn Write, reply: &Reply) {
+    let json = serde_json::to_string(reply).expect("replies always serialize");
+    // Nobody listening is nothing we can do anything about.
+    let _ = writeln!(out, "{}", json).and_then(|_| out.flush());
+}
+
+fn info(decision: &Decision, began: Instant) -> Reply {
+    Reply::Info {
+        millis: began.elapsed().as_millis() as u64,
+        nodes: decision.futures,
+        depth: decision.pv.len(),
+        best: decision.movement,
+        scores: decision.scores,
+        pv: decision.pv.clone(),
+    }
+}
+
+impl Session {
+    pub fn new(config: Config) -> Self {
+        Session {
+            config: config::shared(config),
+            turn: None,
+            games: 0,
+            moves: 0,
+            nodes: 0,
+            millis: 0,
+        }
+    }
+
+    // Acts on one line. Searches read `lines` for a stop while they run.
+    // Returns false once it's time to quit.
+    pub fn handle(&mut self, line: &str, lines: &Receiver<String>, out: &mut dyn Write) -> bool {
+        match parse(line) {
+            Ok(Command::NewGame) => {
+                self.turn = None;
+                self.games += 1;
+                send(out, &Reply::Ready);
+            }
+            Ok(Command::Position(turn)) => self.turn = Some(*turn),
+            Ok(Command::Go(budget)) => match self.turn.clone() {
+                Some(turn) => return self.go(&turn, budget, lines, out),
+                None => send(out, &Reply::Error { message: "go before position".to_string() }),
+            },
+            // Nothing to stop.
+            Ok(Command::Stop) => {}
+            Ok(Command::Stats) => send(
+                out,
+                &Reply::Stats {
+                    games: self.games,
+                    moves: self.moves,
+                    nodes: self.nodes,
+                    millis: self.millis,
+                },
+            ),
+            Ok(Command::Quit) => return false,
+            Err(message) => send(out, &Reply::Error { message }),
+        }
+        true
+    }
+
+    fn go(&mut self, turn: &Turn, budget: Option<Budget>, lines: &Receiver<String>, out: &mut dyn Write) -> bool {
+        let began = Instant::now();
+        let mut running = true;
+        let decision = match budget {
+            Some(Budget::Time(time)) => self.ponder(turn, Some(began + time), lines, out, &mut running),
+            None => self.ponder(turn, None, lines, out, &mut running),
+            Some(budget) => {
+                let decision = search::think(turn, budget, &self.config.load(), &mut search::seeded(turn));
+                send(out, &info(&decision, began));
+                decision
+            }
+        };
+        self.moves += 1;
+        self.nodes += decision.futures as u64;
+        self.millis += began.elapsed().as_millis() as u64;
+        send(out, &Reply::BestMove { movement: decision.movement, turn: turn.turn });
+        running
+    }
+
+    // Workers search in the background until the deadline or a stop, as they do on the server.
+    fn ponder(
+        &mut self,
+        turn: &Turn,
+        deadline: Option<Instant>,
+        lines: &Receiver<String>,
+        out: &mut dyn Write,
+        running: &mut bool,
+    ) -> Decision {
+        let began = Instant::now();
+        let start = search::spawn_workers(turn, &self.config);
+        loop {
+            let wait = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).min(INFO_EVERY),
+                None => INFO_EVERY,
+            };
+            match lines.recv_timeout(wait) {
+                Ok(line) => match parse(&line) {
+                    Ok(Command::Stop) => break,
+                    Ok(Command::Quit) => {
+                        *running = false;
+                        break;
+                    }
+                    Ok(Command::Stats) => {
+                        self.handle(&line, lines, out);
+                    }
+                    _ => send(out, &Reply::Error { message: "still thinking, stop first".to_string() }),
+                },
+                Err(RecvTimeoutError::Timeout) => {}
+                // Nobody left to tell us to stop.
+                Err(RecvTimeoutError::Disconnected) => {
+                    *running = false;
+                    break;
+                }
+            }
+            match deadline {
+                Some(deadline) if Instant::now() >= deadline => break,
+                _ => {}
+            }
+            send(out, &info(&search::decide(turn, &start), began));
+        }
+        search::finish(turn);
+        let decision = search::decide(turn, &start);
+        send(out, &info(&decision, began));
+        decision
+    }
+}
+
+// Reads stdin on its own thread, so a search can keep an ear out for stop.
+fn read_lines() -> Receiver<String> {
+    let (send, recv) = mpsc::channel();
+    std::thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) => {
+                    if send.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+    recv
+}
+
+pub fn main(args: &[String]) {
+    let config = match args {
+        [] => (*config::current()).clone(),
+        [flag, path] if flag == "--config" => Config::from_file(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        }),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+    let mut session = Session::new(config);
+    let lines = read_lines();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    while let Ok(line) = lines.recv() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !session.handle(&line, &lines, &mut out) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake, turn};
+
+    fn duel() -> Turn {
+        let snakes = vec![snake("us", 90, &[(3, 3), (3, 4), (3, 5)]), snake("them", 90, &[(0, 0), (0, 1), (0, 2)])];
+        turn("protocol-test", 3, board(7, 7, snakes, &[(1, 1)]))
+    }
+
+    // Everything but how long it took.
+    fn replies(out: &[u8]) -> Vec<Reply> {
+        String::from_utf8_lossy(out)
+            .lines()
+            .map(|line| match serde_json::from_str(line).unwrap() {
+                Reply::Info { nodes, depth, best, scores, pv, .. } => Reply::Info { millis: 0, nodes, depth, best, scores, pv },
+                Reply::Stats { games, moves, nodes, .. } => Reply::Stats { games, moves, nodes, millis: 0 },
+                reply => reply,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse("go movetime 300"), Ok(Command::Go(Some(Budget::Time(Duration::from_millis(300))))));
+        assert_eq!(parse("go infinite"), Ok(Command::Go(None)));
+        assert_eq!(parse(" stop "), Ok(Command::Stop));
+        assert!(parse("go nodes lots").is_err());
+        assert!(parse("position {").is_err());
+        let json = serde_json::to_string(&duel()).unwrap();
+        assert_eq!(parse(&format!("position {}", json)), Ok(Command::Position(Box::new(duel()))));
+    }
+
+    #[test]
+    fn fixed_budgets_answer_the_same() {
+        let (_send, lines) = mpsc::channel();
+        let position = format!("position {}", serde_json::to_string(&duel()).unwrap());
+        let mut answers = vec![];
+        for _ in 0..2 {
+            let mut session = Session::new(Config::default());
+            let mut out = vec![];
+            for line in ["newgame", &position, "go depth 2", "stats"].iter() {
+                assert!(session.handle(line, &lines, &mut out));
+            }
+            answers.push(replies(&out));
+        }
+        assert_eq!(answers[0], answers[1]);
+        let replies = &answers[0];
+        assert_eq!(replies[0], Reply::Ready);
+        let best = match replies[1] {
+            Reply::Info { best, nodes, .. } => {
+                assert_eq!(nodes, 100);
+                best
+            }
+            ref other => panic!("{:?}", other),
+        };
+        assert_eq!(replies[2], Reply::BestMove { movement: best, turn: duel().turn });
+        assert_eq!(replies[3], Reply::Stats { games: 1, moves: 1, nodes: 100, millis: 0 });
+    }
+
+    #[test]
+    fn stops_when_told() {
+        let (send, lines) = mpsc::channel();
+        let mut session = Session::new(Config::default());
+        let mut out = vec![];
+        session.handle(&format!("position {}", serde_json::to_string(&duel()).unwrap()), &lines, &mut out);
+        send.send("stop".to_string()).unwrap();
+        let began = Instant::now();
+        assert!(session.handle("go infinite", &lines, &mut out));
+        assert!(began.elapsed() < Duration::from_secs(5));
+        match replies(&out).last() {
+            Some(Reply::BestMove { movement, .. }) => assert_ne!(*movement, Movement::Down),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn complains_without_a_position() {
+        let (_send, lines) = mpsc::channel();
+        let mut session = Session::new(Config::default());
+        let mut out = vec![];
+        session.handle("go movetime 10", &lines, &mut out);
+        session.handle("dance", &lines, &mut out);
+        assert!(!session.handle("quit", &lines, &mut out));
+        let replies = replies(&out);
+        assert_eq!(replies.len(), 2);
+        for reply in replies {
+            match reply {
+                Reply::Error { .. } => {}
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+// Where new players start.
+const INITIAL: f64 = 1500.0;
+// Most a rating can move in one game.
+const K: f64 = 32.0;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Rating {
+    pub elo: f64,
+    pub games: u32,
+    pub wins: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating {
+            elo: INITIAL,
+            games: 0,
+            wins: 0,
+        }
+    }
+}
+
+// Multiplayer Elo: every game counts as a match between each pair of players in it,
+// won by whoever lasted longer.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Ratings {
+    pub players: BTreeMap<String, Rating>,
+}
+
+fn expected(us: f64, them: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((them - us) / 400.0))
+}
+
+impl Ratings {
+    // No file yet is the same as no games yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Ratings::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Written to the side and renamed into place, so a crash never leaves half a file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let partial = path.with_extension("partial");
+        std::fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&partial, path)
+    }
+
+    // players[i] lasted survived[i] turns.
+    pub fn update(&mut self, players: &[String], survived: &[u32], winner: Option<usize>) {
+        let before: Vec<f64> = players
+            .iter()
+            .map(|player| self.players.get(player).map_or(INITIAL, |rating| rating.elo))
+            .collect();
+        let mut change = vec![0.0; players.len()];
+        for i in 0..players.len() {
+            for j in 0..players.len() {
+                // Self-play says nothing about how good we are.
+                if i == j || players[i] == players[j] {
+                    continue;
+                }
+                let score = match survived[i].cmp(&survived[j]) {
+                    std::cmp::Ordering::Greater => 1.0,
+                    std::cmp::Ordering::Equal => 0.5,
+                    std::cmp::Ordering::Less => 0.0,
+                };
+                // Spread over the opponents, so bigger games don't swing ratings harder.
+                change[i] += K / (players.len() - 1) as f64 * (score - expected(before[i], before[j]));
+            }
+        }
+        for (idx, player) in players.iter().enumerate() {
+            let rating = self.players.entry(player.clone()).or_default();
+            rating.elo += change[idx];
+            // A player in the game more than once still only played (and won) it once.
+            if players[..idx].contains(player) {
+                continue;
+            }
+            rating.games += 1;
+            if winner.map_or(false, |winner| players[winner] == *player) {
+                rating.wins += 1;
+            }
+        }
+    }
+
+    // Best first.
+    pub fn ranked(&self) -> Vec<(&String, &Rating)> {
+        let mut ranked: Vec<_> = self.players.iter().collect();
+        ranked.sort_by(|a, b| b.1.elo.partial_cmp(&a.1.elo).unwrap());
+        ranked
+    }
+}
+
+pub fn report(ratings: &Ratings) {
+    println!("{:>4} {:<40} {:>7} {:>7} {:>6}", "rank", "player", "elo", "games", "wins");
+    for (rank, (player, rating)) in ratings.ranked().into_iter().enumerate() {
+        println!(
+            "{:>4} {:<40} {:>7.0} {:>7} {:>5.1}%",
+            rank + 1,
+            player,
+            rating.elo,
+            rating.games,
+            100.0 * rating.wins as f64 / rating.games.max(1) as f64
+        );
+    }
+}
+
+pub fn main(args: &[String]) {
+    let path = args.first().map_or(crate::arena::DEFAULT_RATINGS, |path| path.as_str());
+    match Ratings::load(Path::new(path)) {
+        Ok(ratings) => report(&ratings),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn longer_lived_gain() {
+        let mut ratings = Ratings::default();
+        ratings.update(&names(&["a", "b", "c"]), &[50, 20, 50], Some(0));
+        let elo = |name: &str| ratings.players[name].elo;
+        assert!(elo("a") > INITIAL);
+        assert_eq!(elo("a"), elo("c"));
+        assert!(elo("b") < INITIAL);
+        // Elo is only passed around, never made up.
+        assert!((elo("a") + elo("b") + elo("c") - 3.0 * INITIAL).abs() < 1e-9);
+        assert_eq!(ratings.players["a"].wins, 1);
+        assert_eq!(ratings.ranked().last().unwrap().0, "b");
+    }
+
+    #[test]
+    fn self_play_changes_nothing() {
+        let mut ratings = Ratings::default();
+        ratings.update(&names(&["a", "a"]), &[10, 5], Some(0));
+        assert_eq!(ratings.players["a"].elo, INITIAL);
+        // One game, which it won, whichever copy of it lasted longer.
+        assert_eq!(ratings.players["a"].games, 1);
+        assert_eq!(ratings.players["a"].wins, 1);
+        ratings.update(&names(&["a", "b", "a"]), &[3, 5, 10], Some(2));
+        assert_eq!(ratings.players["a"].games, 2);
+        assert_eq!(ratings.players["a"].wins, 2);
+        assert_eq!(ratings.players["b"].games, 1);
+    }
+}
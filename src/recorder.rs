@@ -0,0 +1,226 @@
+use crate::config;
+use crate::logging;
+use crate::requests::Turn;
+use crate::responses::{Move, Start};
+use crate::search::fnv1a;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+// Most game logs kept open at once. Games that never see an /end get closed to make room.
+const MAX_OPEN: usize = 64;
+
+lazy_static! {
+    // Game ids come from the outside world, keep them out of other directories.
+    static ref UNSAFE_CHARS: Regex = Regex::new("[^A-Za-z0-9_-]").unwrap();
+    static ref WRITER: Mutex<Sender<Record>> = {
+        let (send, recv) = channel();
+        std::thread::spawn(move || write_records(recv));
+        Mutex::new(send)
+    };
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Endpoint {
+    Start,
+    Move,
+    End,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(untagged)]
+pub enum Response {
+    Start(Start),
+    Move(Move),
+    Text(String),
+}
+
+// What went into a move.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Stats {
+    pub futures: u32,
+    pub score: u32,
+    // Indexed by Movement::to_int
+    pub scores: [Option<u32>; 4],
+    // Length of the principal variation
+    #[serde(default)]
+    pub depth: u32,
+    // The search's favourite was overruled, or it had nothing to say
+    #[serde(default)]
+    pub fallback: bool,
+}
+
+// One line of a game log.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Record {
+    pub endpoint: Endpoint,
+    pub request: Turn,
+    pub response: Response,
+    #[serde(default)]
+    pub stats: Option<Stats>,
+    // Time spent answering.
+    pub millis: u64,
+}
+
+// Hand a record to the writer thread. Nothing gets built unless recording is on,
+// and the file IO never happens on the request's thread.
+pub fn record<F: FnOnce() -> Record>(make: F) {
+    if !config::current().recording.enabled {
+        return;
+    }
+    let record = make();
+    if let Ok(writer) = WRITER.lock() {
+        let _ = writer.send(record);
+    }
+}
+
+// Named for the game, with a hash of its id so ids that only differ in unsafe characters
+// don't end up in the same file.
+pub fn log_path(dir: &str, game: &str) -> PathBuf {
+    let name = format!("{}-{:08x}", UNSAFE_CHARS.replace_all(game, "_"), fnv1a(game.as_bytes()) as u32);
+    recording_path(dir, &name)
+}
+
+// A recording by the name games lists it under.
+pub fn recording_path(dir: &str, name: &str) -> PathBuf {
+    Path::new(dir).join(format!("{}.jsonl", UNSAFE_CHARS.replace_all(name, "_")))
+}
+
+fn open(path: &Path) -> std::io::Result<File> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn cant_record(path: &Path, record: &Record, e: std::io::Error) {
+    logging::warn("can't record")
+        .turn(&record.request)
+        .field("path", path.display().to_string())
+        .field("error", e.to_string())
+        .emit();
+}
+
+fn write_records(recv: Receiver<Record>) {
+    // Each open file, with when it was last written to.
+    let mut files: HashMap<PathBuf, (File, usize)> = HashMap::new();
+    for (written, record) in recv.into_iter().enumerate() {
+        let path = log_path(&config::current().recording.dir, &record.request.game.id);
+        if !files.contains_key(&path) {
+            let file = match open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    cant_record(&path, &record, e);
+                    continue;
+                }
+            };
+            if files.len() >= MAX_OPEN {
+                let stalest = files.iter().min_by_key(|(_, &(_, last))| last).map(|(path, _)| path.clone());
+                if let Some(stalest) = stalest {
+                    files.remove(&stalest);
+                }
+            }
+            files.insert(path.clone(), (file, 0));
+        }
+        let (file, last) = files.get_mut(&path).expect("just opened");
+        *last = written;
+        let line = serde_json::to_string(&record).expect("records always serialize");
+        if let Err(e) = writeln!(file, "{}", line) {
+            cant_record(&path, &record, e);
+        }
+        if record.endpoint == Endpoint::End {
+            files.remove(&path);
+        }
+    }
+}
+
+// The names of every recording in dir, in order.
+pub fn games(dir: &str) -> std::io::Result<Vec<String>> {
+    let mut games = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "jsonl") {
+            if let Some(stem) = path.file_stem() {
+                games.push(stem.to_string_lossy().into_owned());
+            }
+        }
+    }
+    games.sort();
+    Ok(games)
+}
+
+// Everything recorded in a game log, in order.
+pub fn read(path: &Path) -> std::io::Result<Vec<Record>> {
+    let mut records = vec![];
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str(&line)?);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake, turn};
+    use crate::responses::Movement;
+
+    #[test]
+    fn round_trip() {
+        let you = snake("snake-id-string", 90, &[(1, 3)]);
+        let record = Record {
+            endpoint: Endpoint::Move,
+            request: turn("game-id-string", 4, board(15, 15, vec![you], &[])),
+            response: Response::Move(Move::new(Movement::Up, "hi".to_string())),
+            stats: Some(Stats {
+                futures: 10,
+                score: 3,
+                scores: [None, None, Some(3), Some(2)],
+                depth: 4,
+                fallback: false,
+            }),
+            millis: 450,
+        };
+        let line = serde_json::to_string(&record).unwrap();
+        assert_eq!(serde_json::from_str::<Record>(&line).unwrap(), record);
+    }
+
+    #[test]
+    fn keeps_paths_in_dir() {
+        let path = log_path("games", "../../etc/passwd");
+        assert_eq!(path.parent(), Some(Path::new("games")));
+        assert!(path.to_str().unwrap().starts_with("games/______etc_passwd-"));
+        assert_eq!(recording_path("games", "../x"), Path::new("games/___x.jsonl"));
+    }
+
+    #[test]
+    fn ids_get_their_own_files() {
+        assert_ne!(log_path("games", "a/b"), log_path("games", "a_b"));
+        assert_eq!(log_path("games", "a/b"), log_path("games", "a/b"));
+        // Listed by name, and found again by it.
+        let name = log_path("games", "a/b").file_stem().unwrap().to_str().unwrap().to_string();
+        assert_eq!(recording_path("games", &name), log_path("games", "a/b"));
+    }
+
+    #[test]
+    fn lists_games() {
+        let dir = std::env::temp_dir().join(format!("snek-games-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.jsonl", "a.jsonl", "notes.txt"].iter() {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let games = games(dir.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(games.unwrap(), vec!["a", "b"]);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::config::{self, Config, CONFIG};
+use crate::logging;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+// Config reloading: a new config swapped in while the server runs, when the file changes or on
+// POST /admin/reload. Games in progress carry on, and their workers pick the new config up on their
+// next trip into the future. What's served and where logs go are only read at startup, so changes to
+// those get a warning instead.
+
+// The fields only read at startup that differ between `old` and `new`.
+pub fn needs_restart(old: &Config, new: &Config) -> Vec<&'static str> {
+    let mut fields = vec![];
+    if old.snakes != new.snakes {
+        fields.push("snakes");
+    }
+    if old.serve_bots != new.serve_bots {
+        fields.push("serve_bots");
+    }
+    if old.logging.path != new.logging.path {
+        fields.push("logging.path");
+    }
+    fields
+}
+
+// Read `path` and swap it in for whatever `config` held, saying which changes won't take until a
+// restart. A bad file leaves the old config in place.
+pub fn reload(config: &ArcSwap<Config>, path: &str) -> Result<Vec<&'static str>, String> {
+    let fresh = Config::from_file(path)?;
+    let ignored = needs_restart(&config.load(), &fresh);
+    config.store(Arc::new(fresh));
+    Ok(ignored)
+}
+
+// The server's config, from wherever it was loaded from.
+pub fn reload_server() -> Result<(), String> {
+    let path = Config::path();
+    match reload(&CONFIG, &path) {
+        Ok(ignored) => {
+            logging::info("config reloaded").field("path", &path).emit();
+            for field in ignored {
+                logging::warn("config change needs a restart").field("path", &path).field("field", field).emit();
+            }
+            Ok(())
+        }
+        Err(e) => {
+            logging::warn("config not reloaded").field("path", &path).field("error", &e).emit();
+            Err(e)
+        }
+    }
+}
+
+// Whether the Authorization header has the admin token. There's no way in without a token configured.
+pub fn authorized(header: Option<&str>, token: &Option<String>) -> bool {
+    let (header, token) = match (header, token) {
+        (Some(header), Some(token)) if !token.is_empty() => (header, token),
+        _ => return false,
+    };
+    let expected = format!("Bearer {}", token);
+    // Every byte gets looked at, so how long this takes says nothing about how much was right.
+    header.len() == expected.len() && header.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// When a file was last changed, to notice the next time.
+pub struct Watcher {
+    path: String,
+    modified: Option<SystemTime>,
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl Watcher {
+    pub fn new(path: &str) -> Self {
+        Watcher {
+            path: path.to_string(),
+            modified: modified(path),
+        }
+    }
+
+    // Whether the file's changed since last time. A file that's gone hasn't changed.
+    pub fn changed(&mut self) -> bool {
+        match modified(&self.path) {
+            Some(now) if Some(now) != self.modified => {
+                self.modified = Some(now);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// While watching's off, how often to look at whether a reload turned it back on.
+const IDLE: Duration = Duration::from_secs(1);
+
+// Check the server's config file for changes every so often, as the config says.
+pub fn watch() {
+    let mut watcher = Watcher::new(&Config::path());
+    std::thread::spawn(move || loop {
+        let every = config::current().reload.watch_millis;
+        if every == 0 {
+            std::thread::sleep(IDLE);
+            continue;
+        }
+        std::thread::sleep(Duration::from_millis(every));
+        if watcher.changed() {
+            // Complaints about a bad file have been logged, and the old config is still good.
+            let _ = reload_server();
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn checks_tokens() {
+        let token = Some("s3cret".to_string());
+        assert!(authorized(Some("Bearer s3cret"), &token));
+        assert!(!authorized(Some("Bearer s3cre"), &token));
+        assert!(!authorized(Some("Bearer s3creT"), &token));
+        assert!(!authorized(None, &token));
+        assert!(!authorized(Some("Bearer "), &Some(String::new())));
+        assert!(!authorized(Some("Bearer s3cret"), &None));
+    }
+
+    #[test]
+    fn reloads_good_files_only() {
+        let dir = std::env::temp_dir().join(format!("snek-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snek.json").display().to_string();
+        let mut watcher = Watcher::new(&path);
+        assert!(!watcher.changed());
+
+        let config = ArcSwap::from_pointee(Config::default());
+        fs::write(&path, r#"{"search": {"workers": 7, "explore_depth": 3}}"#).unwrap();
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+        assert_eq!(reload(&config, &path).unwrap(), Vec::<&str>::new());
+        assert_eq!(config.load().search.workers, 7);
+        assert_eq!(config.load().search.explore_depth, 3);
+        assert_eq!(config.load().search.think_millis, Config::default().search.think_millis);
+
+        // Taken, but only a restart serves it.
+        fs::write(&path, r#"{"search": {"workers": 7}, "serve_bots": true, "logging": {"path": null}}"#).unwrap();
+        assert_eq!(reload(&config, &path).unwrap(), vec!["serve_bots", "logging.path"]);
+        assert!(config.load().serve_bots);
+
+        fs::write(&path, "{\"search\": ").unwrap();
+        assert!(reload(&config, &path).is_err());
+        assert_eq!(config.load().search.workers, 7);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use crate::config;
+use crate::dot;
+use crate::recorder::{self, Endpoint, Response};
+use crate::search::{self, Budget};
+use std::path::Path;
+use std::process::exit;
+use std::time::Duration;
+
+const USAGE: &str = "usage: rusty_snake replay <game.jsonl>... [--nodes N | --millis N | --depth N]
+    [--dot TURN [--dot-depth N]]";
+
+// Enough to see the search's opinion without waiting all day.
+const DEFAULT_NODES: u32 = 2000;
+
+fn number(arg: Option<&String>) -> u32 {
+    match arg.and_then(|arg| arg.parse().ok()) {
+        Some(n) => n,
+        None => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    }
+}
+
+// Think about every recorded move again, and point out where we'd now do something else.
+pub fn main(args: &[String]) {
+    let mut budget = Budget::Nodes(DEFAULT_NODES);
+    let mut dot_turn = None;
+    let mut dot_depth = dot::DEFAULT_DEPTH;
+    let mut paths = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--nodes" => budget = Budget::Nodes(number(args.next())),
+            "--millis" => budget = Budget::Time(Duration::from_millis(number(args.next()) as u64)),
+            "--depth" => budget = Budget::Depth(number(args.next())),
+            "--dot" => dot_turn = Some(number(args.next())),
+            "--dot-depth" => dot_depth = number(args.next()),
+            path => paths.push(path),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+    for path in paths {
+        let result = match dot_turn {
+            Some(turn) => tree(Path::new(path), budget, turn, dot_depth),
+            None => replay(Path::new(path), budget),
+        };
+        if let Err(e) = result {
+            eprintln!("{}: {}", path, e);
+            exit(1);
+        }
+    }
+}
+
+pub fn replay(path: &Path, budget: Budget) -> std::io::Result<()> {
+    let mut turns = 0;
+    let mut differ = 0;
+    for record in recorder::read(path)? {
+        let was = match (record.endpoint, &record.response) {
+            (Endpoint::Move, Response::Move(movement)) => movement.movement(),
+            _ => continue,
+        };
+        let now = search::think(&record.request, budget, &config::current(), &mut search::seeded(&record.request));
+        turns += 1;
+        let flag = if now.movement != was {
+            differ += 1;
+            "  <-- differs"
+        } else {
+            ""
+        };
+        let pv: Vec<String> = now.pv.iter().map(|dir| dir.to_string()).collect();
+        println!(
+            "turn {:>4}: now {:<5} was {:<5} (score {}, {} futures, pv {}){}",
+            record.request.turn,
+            now.movement,
+            was,
+            now.score(),
+            now.futures,
+            pv.join(" "),
+            flag
+        );
+    }
+    println!("{}: {} of {} turns differ", path.display(), differ, turns);
+    Ok(())
+}
+
+// The search tree for one recorded turn, as DOT on stdout.
+pub fn tree(path: &Path, budget: Budget, turn: u32, depth: u32) -> std::io::Result<()> {
+    let record = recorder::read(path)?
+        .into_iter()
+        .find(|record| record.endpoint == Endpoint::Move && record.request.turn == turn)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("no move on turn {}", turn)))?;
+    let request = &record.request;
+    let root = search::grow(request, budget, &config::current(), &mut search::seeded(request));
+    print!("{}", dot::render(&request.you.id, &root, depth));
+    Ok(())
+}
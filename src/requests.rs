@@ -1,11 +1,18 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
 }
 
+impl Point {
+    // Manhattan distance, i.e. how many moves apart.
+    pub fn distance(self, other: Self) -> i32 {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
+    }
+}
+
 impl std::ops::Add for Point {
     type Output = Self;
     fn add(self, other: Self) -> Self {
@@ -25,7 +32,7 @@ impl std::ops::Sub for Point {
         }
     }
 }
-#[derive(Deserialize, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct Turn {
     pub game: Game,
     pub turn: u32,
@@ -33,12 +40,12 @@ pub struct Turn {
     pub you: Snake,
 }
 
-#[derive(Deserialize, PartialEq, Eq, Debug, Hash, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Hash, Clone)]
 pub struct Game {
     pub id: String,
 }
 
-#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct Board {
     pub height: i32,
     pub width: i32,
@@ -46,7 +53,7 @@ pub struct Board {
     pub snakes: Vec<Snake>,
 }
 
-#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct Snake {
     pub id: String,
     pub name: String,
@@ -57,6 +64,46 @@ pub struct Snake {
     pub shout: Option<String>,
 }
 
+// Boards and turns for tests, with cells as (x, y) pairs.
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    pub fn points(cells: &[(i32, i32)]) -> Vec<Point> {
+        cells.iter().map(|&(x, y)| Point { x, y }).collect()
+    }
+
+    // Named after its id, head first.
+    pub fn snake(id: &str, health: i32, body: &[(i32, i32)]) -> Snake {
+        Snake {
+            id: id.to_string(),
+            name: id.to_string(),
+            health,
+            body: points(body),
+            shout: None,
+        }
+    }
+
+    pub fn board(width: i32, height: i32, snakes: Vec<Snake>, food: &[(i32, i32)]) -> Board {
+        Board {
+            height,
+            width,
+            food: points(food),
+            snakes,
+        }
+    }
+
+    // The first snake on the board is us.
+    pub fn turn(game: &str, turn: u32, board: Board) -> Turn {
+        Turn {
+            game: Game { id: game.to_string() },
+            turn,
+            you: board.snakes[0].clone(),
+            board,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
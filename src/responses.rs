@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct Start {
     color: String,
     #[serde(rename = "headType")]
@@ -20,7 +20,7 @@ impl Start {
 }
 
 // TODO: Make all the head types
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum HeadType {
     Regular,
@@ -40,7 +40,7 @@ pub enum HeadType {
 }
 
 // TODO: Make all the tail types
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum TailType {
     Regular,
@@ -61,10 +61,12 @@ pub enum TailType {
     SmallRattle,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct Move {
     #[serde(rename = "move")]
     movement: Movement,
+    // Other snakes don't always have something to say.
+    #[serde(default)]
     shout: String,
 }
 
@@ -72,6 +74,10 @@ impl Move {
     pub fn new(movement: Movement, shout: String) -> Move {
         Move { movement, shout }
     }
+
+    pub fn movement(&self) -> Movement {
+        self.movement
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
@@ -123,12 +129,25 @@ impl Movement{
             Point{x:-1, y: 0} => Left,
             Point{x: 0, y:-1} => Up,
             Point{x: 0, y: 1} => Down,
-            Point{x: 0, y: 0} => {println!("WARNING: Origin Offset. {:?}", offset); Right},
+            Point{x: 0, y: 0} => {crate::logging::warn("origin offset").emit(); Right},
             offset => panic!("Invalid offset! {:?}", offset)
         }
     }
 }
 
+impl std::fmt::Display for Movement {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use Movement::*;
+        // pad, so widths line up in tables
+        f.pad(match self {
+            Right => "right",
+            Left => "left",
+            Up => "up",
+            Down => "down",
+        })
+    }
+}
+
 impl From<usize> for Movement {
     fn from(int: usize) -> Movement {
         Self::ALL[int]
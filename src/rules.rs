@@ -0,0 +1,260 @@
+use crate::requests::{Board, Point, Snake};
+use crate::responses::Movement;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Ruleset {
+    Standard,
+    // Played alone, the game lasts until the last snake dies.
+    Solo,
+    // No food, everyone grows every turn.
+    Constrictor,
+}
+
+impl std::str::FromStr for Ruleset {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Self, String> {
+        match name {
+            "standard" => Ok(Ruleset::Standard),
+            "solo" => Ok(Ruleset::Solo),
+            "constrictor" => Ok(Ruleset::Constrictor),
+            _ => Err(format!("unknown ruleset {}", name)),
+        }
+    }
+}
+
+impl Ruleset {
+    pub fn is_over(self, board: &Board) -> bool {
+        match self {
+            Ruleset::Solo => board.snakes.is_empty(),
+            _ => board.snakes.len() <= 1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Food {
+    // Always keep at least this much on the board.
+    pub min: usize,
+    // Percent chance of another one each turn.
+    pub chance: u32,
+}
+
+impl Default for Food {
+    fn default() -> Self {
+        Food { min: 1, chance: 15 }
+    }
+}
+
+fn outside(board: &Board, pos: Point) -> bool {
+    pos.x < 0 || pos.y < 0 || pos.x >= board.width || pos.y >= board.height
+}
+
+// Move every snake (moves[i] is for board.snakes[i]), feed them, and work out who died.
+// The dead are left on the board for the caller to deal with.
+pub fn step(board: &mut Board, moves: &[Movement], ruleset: Ruleset) -> Vec<bool> {
+    for (snake, dir) in board.snakes.iter_mut().zip(moves.iter()) {
+        let head = snake.body[0] + dir.to_offset();
+        snake.body.insert(0, head);
+        snake.body.pop();
+        snake.health -= 1;
+    }
+
+    // Eating grows the tail now, so it stays put next turn.
+    let heads: Vec<Point> = board.snakes.iter().map(|snake| snake.body[0]).collect();
+    for snake in board.snakes.iter_mut() {
+        if ruleset == Ruleset::Constrictor || board.food.contains(&snake.body[0]) {
+            let tail = *snake.body.last().unwrap();
+            snake.body.push(tail);
+            snake.health = 100;
+        }
+    }
+    board.food.retain(|food| !heads.contains(food));
+
+    // Starving or leaving the board happens first, and those snakes don't get in anyone's way.
+    let gone: Vec<bool> = board
+        .snakes
+        .iter()
+        .map(|snake| snake.health <= 0 || outside(board, snake.body[0]))
+        .collect();
+    let mut dead = gone.clone();
+    for (idx, snake) in board.snakes.iter().enumerate() {
+        if gone[idx] {
+            continue;
+        }
+        let head = snake.body[0];
+        for (oidx, other) in board.snakes.iter().enumerate() {
+            if gone[oidx] {
+                continue;
+            }
+            let bitten = other.body.iter().skip(1).any(|&piece| piece == head);
+            // head-to-head, the longer snake wins
+            let beaten = oidx != idx && other.body[0] == head && other.body.len() >= snake.body.len();
+            if bitten || beaten {
+                dead[idx] = true;
+            }
+        }
+    }
+    dead
+}
+
+pub fn remove_dead(board: &mut Board, dead: &[bool]) {
+    let mut idx = 0;
+    board.snakes.retain(|_| {
+        idx += 1;
+        !dead[idx - 1]
+    });
+}
+
+fn empty_cells(board: &Board) -> Vec<Point> {
+    let mut cells = vec![];
+    for y in 0..board.height {
+        for x in 0..board.width {
+            let pos = Point { x, y };
+            let taken = board.food.contains(&pos)
+                || board.snakes.iter().any(|snake| snake.body.contains(&pos));
+            if !taken {
+                cells.push(pos);
+            }
+        }
+    }
+    cells
+}
+
+pub fn spawn_food<R: Rng>(board: &mut Board, food: &Food, rng: &mut R) {
+    let mut wanted = food.min.saturating_sub(board.food.len());
+    if wanted == 0 && rng.gen_range(0, 100) < food.chance {
+        wanted = 1;
+    }
+    let mut cells = empty_cells(board);
+    cells.shuffle(rng);
+    board.food.extend(cells.into_iter().take(wanted));
+}
+
+// A fresh board with snakes at the usual starting spots (or anywhere, if those won't do),
+// each three long and curled up on one cell.
+pub fn setup<R: Rng>(width: i32, height: i32, ids: &[String], food: &Food, rng: &mut R) -> Board {
+    let (w, h) = (width, height);
+    let mut spots = vec![
+        (1, 1),
+        (w - 2, h - 2),
+        (1, h - 2),
+        (w - 2, 1),
+        (w / 2, 1),
+        (w / 2, h - 2),
+        (1, h / 2),
+        (w - 2, h / 2),
+    ];
+    if ids.len() > spots.len() || w < 7 || h < 7 {
+        spots = (0..h).flat_map(|y| (0..w).map(move |x| (x, y))).collect();
+    }
+    spots.shuffle(rng);
+
+    let mut board = Board {
+        height,
+        width,
+        food: vec![],
+        snakes: ids
+            .iter()
+            .zip(spots)
+            .map(|(id, (x, y))| Snake {
+                id: id.clone(),
+                name: id.clone(),
+                health: 100,
+                body: vec![Point { x, y }; 3],
+                shout: None,
+            })
+            .collect(),
+    };
+    spawn_food(
+        &mut board,
+        &Food {
+            min: ids.len().max(food.min),
+            chance: 0,
+        },
+        rng,
+    );
+    board
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake};
+
+    #[test]
+    fn eating_grows() {
+        let mut board = board(5, 5, vec![snake("a", 50, &[(1, 1), (1, 2), (1, 3)])], &[(2, 1)]);
+        let dead = step(&mut board, &[Movement::Right], Ruleset::Standard);
+        assert_eq!(dead, vec![false]);
+        assert!(board.food.is_empty());
+        let a = &board.snakes[0];
+        assert_eq!(a.health, 100);
+        assert_eq!(a.body, vec![Point { x: 2, y: 1 }, Point { x: 1, y: 1 }, Point { x: 1, y: 2 }, Point { x: 1, y: 2 }]);
+    }
+
+    #[test]
+    fn collisions() {
+        let mut board = board(
+            5,
+            5,
+            vec![
+                snake("wall", 50, &[(0, 0), (0, 1)]),
+                snake("long", 50, &[(2, 2), (3, 2), (4, 2)]),
+                snake("short", 50, &[(2, 4), (3, 4)]),
+                snake("biter", 50, &[(3, 1), (4, 1)]),
+            ],
+            &[],
+        );
+        let moves = [Movement::Left, Movement::Down, Movement::Up, Movement::Down];
+        // wall leaves the board, long beats short head-to-head at (2, 3), biter runs into long.
+        let dead = step(&mut board, &moves, Ruleset::Standard);
+        assert_eq!(dead, vec![true, false, true, true]);
+        remove_dead(&mut board, &dead);
+        assert_eq!(board.snakes.len(), 1);
+        assert!(Ruleset::Standard.is_over(&board));
+        assert!(!Ruleset::Solo.is_over(&board));
+    }
+
+    #[test]
+    fn head_to_head_goes_by_length() {
+        // Length wins, however hungry the longer snake is.
+        let mut board = board(
+            5,
+            5,
+            vec![snake("full", 100, &[(1, 2), (0, 2)]), snake("long", 10, &[(3, 2), (4, 2), (4, 3)])],
+            &[],
+        );
+        let dead = step(&mut board, &[Movement::Right, Movement::Left], Ruleset::Standard);
+        assert_eq!(dead, vec![true, false]);
+
+        // Nobody wins a tie.
+        let mut board = self::board(
+            5,
+            5,
+            vec![snake("a", 50, &[(1, 2), (0, 2)]), snake("b", 50, &[(3, 2), (4, 2)])],
+            &[],
+        );
+        let dead = step(&mut board, &[Movement::Right, Movement::Left], Ruleset::Standard);
+        assert_eq!(dead, vec![true, true]);
+
+        // And nobody meets their own head.
+        let mut board = self::board(5, 5, vec![snake("a", 50, &[(1, 2), (0, 2)])], &[]);
+        assert_eq!(step(&mut board, &[Movement::Right], Ruleset::Standard), vec![false]);
+    }
+
+    #[test]
+    fn keeps_food_topped_up() {
+        let mut rng = rand::thread_rng();
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let mut board = setup(11, 11, &ids, &Food::default(), &mut rng);
+        assert_eq!(board.snakes.len(), 2);
+        assert_eq!(board.food.len(), 2);
+        board.food.clear();
+        spawn_food(&mut board, &Food { min: 3, chance: 0 }, &mut rng);
+        assert_eq!(board.food.len(), 3);
+    }
+}
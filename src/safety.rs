@@ -0,0 +1,114 @@
+use crate::analysis::Grid;
+use crate::requests::{Board, Point, Snake};
+use crate::responses::Movement;
+
+// Worst to best, so the safest move is the biggest.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum Safety {
+    // Wall, body, or starving.
+    Death,
+    // A snake at least as long as us can move there too.
+    HeadToHead,
+    Safe,
+}
+
+// A tail stays put the turn after its snake eats.
+fn tail_moves(snake: &Snake) -> bool {
+    let len = snake.body.len();
+    snake.health != 100 && (len < 2 || snake.body[len - 1] != snake.body[len - 2])
+}
+
+fn occupied(board: &Board, pos: Point) -> bool {
+    board.snakes.iter().any(|snake| {
+        let keep = if tail_moves(snake) { snake.body.len() - 1 } else { snake.body.len() };
+        snake.body.iter().take(keep).any(|&piece| piece == pos)
+    })
+}
+
+// How each move (indexed by Movement::to_int) works out for snake `me` this turn.
+pub fn assess(board: &Board, me: usize) -> [Safety; 4] {
+    let us = &board.snakes[me];
+    let head = us.body[0];
+    let mut safety = [Safety::Safe; 4];
+    for &dir in Movement::ALL.iter() {
+        let next = head + dir.to_offset();
+        let outside = next.x < 0 || next.y < 0 || next.x >= board.width || next.y >= board.height;
+        let starving = us.health <= 1 && !board.food.contains(&next);
+        safety[dir.to_int()] = if outside || starving || occupied(board, next) {
+            Safety::Death
+        } else if board.snakes.iter().enumerate().any(|(idx, snake)| {
+            idx != me && snake.body.len() >= us.body.len() && snake.body[0].distance(next) == 1
+        }) {
+            Safety::HeadToHead
+        } else {
+            Safety::Safe
+        };
+    }
+    safety
+}
+
+// The safest move, then the best search score, then the most room.
+// So the search gets its way unless it wants something more dangerous than it has to.
+pub fn pick(board: &Board, me: usize, scores: &[Option<u32>; 4]) -> Movement {
+    let safety = assess(board, me);
+    let grid = Grid::new(board);
+    let head = board.snakes[me].body[0];
+    Movement::ALL
+        .iter()
+        .cloned()
+        .max_by_key(|dir| {
+            let idx = dir.to_int();
+            (safety[idx], scores[idx], grid.reachable(head + dir.to_offset()))
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake};
+
+    #[test]
+    fn ranks_moves() {
+        let board = board(
+            5,
+            5,
+            vec![
+                snake("us", 90, &[(4, 2), (3, 2), (2, 2)]),
+                snake("them", 90, &[(3, 1), (2, 1), (1, 1), (0, 1)]),
+            ],
+            &[],
+        );
+        let safety = assess(&board, 0);
+        assert_eq!(safety[Movement::Right.to_int()], Safety::Death);
+        assert_eq!(safety[Movement::Left.to_int()], Safety::Death);
+        assert_eq!(safety[Movement::Up.to_int()], Safety::HeadToHead);
+        assert_eq!(safety[Movement::Down.to_int()], Safety::Safe);
+    }
+
+    #[test]
+    fn tails_move_unless_fed() {
+        let mut board = board(
+            5,
+            5,
+            vec![
+                snake("us", 90, &[(1, 1), (1, 2)]),
+                snake("them", 90, &[(3, 2), (3, 1), (2, 1)]),
+            ],
+            &[],
+        );
+        assert_eq!(assess(&board, 0)[Movement::Right.to_int()], Safety::Safe);
+        board.snakes[1].health = 100;
+        assert_eq!(assess(&board, 0)[Movement::Right.to_int()], Safety::Death);
+    }
+
+    #[test]
+    fn overrules_search_into_wall() {
+        let board = board(5, 5, vec![snake("us", 90, &[(4, 2), (3, 2)])], &[]);
+        let scores = [Some(500), None, Some(10), None];
+        assert_eq!(pick(&board, 0, &scores), Movement::Up);
+        // Nothing from the search at all still gets a safe move.
+        let dir = pick(&board, 0, &[None; 4]);
+        assert_eq!(assess(&board, 0)[dir.to_int()], Safety::Safe);
+    }
+}
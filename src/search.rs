@@ -0,0 +1,808 @@
+use crate::{endgame, requests, responses, rules, safety, solo};
+use crate::aggression;
+use crate::analysis;
+use crate::config::{Config, Shared};
+use crate::metrics;
+use crate::requests::Board;
+use crate::responses::Movement;
+use lazy_static::lazy_static;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub struct SnekStep{
+    generation: u32,
+    // TODO: Atomics?
+    historic: Mutex<bool>,
+    // for starts, nobody else can reach us and there's little enough room to solve exactly
+    sealed: bool,
+    // handed over to the endgame solver
+    solved: Mutex<bool>,
+    score: Mutex<u32>,
+    children: Mutex<u32>,
+    // workers exploring from here, for starts
+    busy: Mutex<u32>,
+    board: Board,
+    dirs: [Mutex<Option<Arc<SnekStep>>>; 4]
+}
+
+impl SnekStep{
+    fn new(generation: u32, board: Board) -> Self{
+        SnekStep{
+            generation,
+            board,
+            historic: Mutex::new(false),
+            sealed: false,
+            solved: Mutex::new(false),
+            score: Mutex::new(0),
+            children: Mutex::new(0),
+            busy: Mutex::new(0),
+            dirs: [Mutex::new(None), Mutex::new(None), Mutex::new(None), Mutex::new(None)],
+        }
+    }
+
+    pub fn board(&self) -> &Board{
+        &self.board
+    }
+
+    pub fn score(&self) -> u32{
+        *self.score.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Futures looked at from here.
+    pub fn visits(&self) -> u32{
+        *self.children.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Workers exploring from here right now, rather than sitting idle.
+    pub fn busy(&self) -> u32{
+        *self.busy.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Our moves from here that have been looked at, and where they led.
+    pub fn expanded(&self) -> Vec<(Movement, Arc<SnekStep>)>{
+        Movement::ALL.iter().filter_map(|&dir| {
+            let child = self.dirs[dir.to_int()].lock().unwrap_or_else(|e| e.into_inner());
+            child.as_ref().map(|child| (dir, child.clone()))
+        }).collect()
+    }
+}
+
+// Which game, and which of our snakes in it.
+pub type Key = (requests::Game, String);
+
+lazy_static! {
+    pub static ref CURRENT_STEP: Mutex<HashMap<Key, Arc<SnekStep>>> = Mutex::new(HashMap::new());
+}
+
+fn key(turn: &requests::Turn) -> Key {
+    (turn.game.clone(), turn.you.id.clone())
+}
+
+// solved endgames beat anything a rollout can score
+const ENDGAME_SCORE: u32 = 1_000_000;
+
+fn restart(key: &Key, steps: &mut Vec<Arc<SnekStep>>) -> Option<Arc<SnekStep>>{
+    if let Some(start) = CURRENT_STEP.lock().unwrap().get(key){
+        let start = start.clone();
+        steps.clear();
+        steps.push(start.clone());
+        Some(start)
+    }else{
+        None
+    }
+}
+
+// Score each first move by exactly how long it lets us survive.
+// The first worker here solves it, without holding anyone else up while it does.
+fn solve_endgame(start: &SnekStep, snake_idx: usize, config: &Config){
+    {
+        let mut solved = start.solved.lock().unwrap();
+        if *solved { return; }
+        *solved = true;
+    }
+    let turns = endgame::solve(&start.board, snake_idx, config.endgame.node_limit);
+    for (&dir, turns) in responses::Movement::ALL.iter().zip(turns.iter()){
+        if let Some(turns) = turns{
+            let board = endgame::advance(&start.board, snake_idx, dir);
+            let child = Arc::new(SnekStep::new(start.generation + 1, board));
+            *child.score.lock().unwrap() = ENDGAME_SCORE + turns;
+            *start.dirs[dir.to_int()].lock().unwrap() = Some(child);
+        }
+    }
+}
+
+// Worker number `worker` sits idle while the config wants no more than that many.
+pub fn thread_work(key: &Key, worker: u32, config: &Shared) -> Option<()>{
+    let mut rng = rand::thread_rng();
+    let mut steps: Vec<Arc<SnekStep>> = vec![];
+    let mut start = restart(key, &mut steps)?;
+    let mut busy = false;
+    loop{
+        // 1. Update to latest step
+        if *start.historic.lock().unwrap(){
+            start = restart(key, &mut steps)?;
+            busy = false;
+        }
+        // every time round, so a reloaded config reaches games already going.
+        let config = config.load();
+        if busy != (worker < config.search.workers){
+            busy = !busy;
+            let mut count = start.busy.lock().unwrap();
+            if busy { *count += 1 } else { *count -= 1 }
+        }
+        if !busy{
+            std::thread::sleep(Duration::from_millis(5));
+            continue;
+        }
+        if explore(&start, &mut steps, &key.1, &mut rng, &config){
+            std::thread::yield_now();
+        }else{
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+// Everyone moves once, us in `dir` and the others as the opponent model says.
+// Returns the new board and where we are in it, or 100,000 if we died.
+fn simulate<R: Rng>(board: &Board, snake_idx: usize, dir: Movement, rng: &mut R, config: &Config) -> (Board, usize){
+    // 2.2.1 Decide on directions for all the snakes to move in.
+    let mut all_dirs = vec![];
+    for idx in 0..board.snakes.len(){
+        all_dirs.push(if idx==snake_idx{
+            dir
+        }else{
+            match config.search.opponent_model{
+                Some(bot) => bot.choose(board, idx, rng),
+                None => *Movement::ALL.choose(rng).unwrap(),
+            }
+        });
+    }
+    play(board, snake_idx, &all_dirs)
+}
+
+// Everyone moves once, all_dirs[i] being board.snakes[i]'s move. Returns as simulate does.
+fn play(board: &Board, snake_idx: usize, all_dirs: &[Movement]) -> (Board, usize){
+    let mut new_board = board.clone();
+    // 2.2.2 Move snakes, eat food, and check for collisions
+    let dead = rules::step(&mut new_board, all_dirs, rules::Ruleset::Standard);
+    // 2.2.3 KILL SNAKES
+    // killing snakes will shrink snake_idx sometimes. or set it to 100,000 if we died
+    let snake_idx = if dead[snake_idx] {
+        100_000
+    } else {
+        snake_idx - dead[..snake_idx].iter().filter(|&&d| d).count()
+    };
+    rules::remove_dead(&mut new_board, &dead);
+    (new_board, snake_idx)
+}
+
+// How much we like being snake `snake_idx` on this board, `generation` turns in.
+fn evaluate(board: &Board, snake_idx: usize, generation: u32, config: &Config) -> u32{
+    let weights = &config.weights;
+    let weigh = |term: u32, weight: u32| term * weight / 100;
+    let mut score = generation;
+    let snek = &board.snakes[snake_idx];
+    let head = snek.body[0];
+    let dx = head.x - board.width/2;
+    let dy = head.y - board.height/2;
+    // big boards go below zero in the corners
+    score += weigh((100 - dx*dx - dy*dy).max(0) as u32 / 4, weights.centre);
+    score += weigh(snek.health as u32, weights.health);
+    // room to move, minus pockets that can be sealed, plus opponents we've cut off
+    score += weigh(analysis::space_score(board, snake_idx), weights.space);
+    // go after snakes we can bully
+    score += weigh(aggression::hunt_score(board, snake_idx, &config.aggression), weights.hunt);
+    score
+}
+
+// Hands start to the endgame solver if it's sealed.
+fn try_endgame(start: &SnekStep, snake_id: &str, config: &Config) -> bool{
+    if start.sealed{
+        let start_idx = start.board.snakes.iter().position(|snake| snake.id==snake_id).unwrap();
+        solve_endgame(start, start_idx, config);
+    }
+    start.sealed
+}
+
+// Every move of every snake in `dir`, us making `dir`, all_dirs[i] being board.snakes[i]'s move.
+fn joint_moves(snakes: usize, snake_idx: usize, dir: Movement) -> Vec<Vec<Movement>>{
+    (0..4usize.pow(snakes as u32 - 1)).map(|mut code| {
+        (0..snakes).map(|idx| if idx == snake_idx{
+            dir
+        }else{
+            let theirs = Movement::ALL[code % 4];
+            code /= 4;
+            theirs
+        }).collect()
+    }).collect()
+}
+
+// Every one of our moves against every combination of the others' moves, `depth` deep.
+// Each of our moves is worth what its worst reply leaves us, nothing if some reply kills us,
+// and only that reply is kept in the tree. Leaves are scored as explore scores them and the
+// best of our moves is kept going up. Returns how many futures were looked at.
+fn expand(step: &SnekStep, snake_idx: usize, depth: u32, config: &Config) -> u32{
+    let mut futures = 0;
+    let mut best = None;
+    if depth > 0{
+        for &dir in Movement::ALL.iter(){
+            let mut worst: Option<Arc<SnekStep>> = None;
+            let mut killed = false;
+            for all_dirs in joint_moves(step.board.snakes.len(), snake_idx, dir){
+                futures += 1;
+                let (board, idx) = play(&step.board, snake_idx, &all_dirs);
+                // no future for dead snakes.
+                if idx > 1000 {
+                    killed = true;
+                    continue;
+                }
+                let child = Arc::new(SnekStep::new(step.generation + 1, board));
+                futures += expand(&child, idx, depth - 1, config);
+                let worse = match worst{
+                    Some(ref worst) => child.score() < worst.score(),
+                    None => true,
+                };
+                if worse{
+                    worst = Some(child);
+                }
+            }
+            if let Some(child) = worst{
+                if killed{
+                    *child.score.lock().unwrap() = 0;
+                }
+                best = best.max(Some(child.score()));
+                *step.dirs[dir.to_int()].lock().unwrap() = Some(child);
+            }
+        }
+    }
+    *step.score.lock().unwrap() = best.unwrap_or_else(|| evaluate(&step.board, snake_idx, step.generation, config));
+    *step.children.lock().unwrap() = futures;
+    futures
+}
+
+// One trip into the future from the end of `steps`, scored and applied back up the path.
+// Returns false if there's nothing left to explore under this start.
+fn explore<R: Rng>(start: &Arc<SnekStep>, steps: &mut Vec<Arc<SnekStep>>, snake_id: &str, rng: &mut R, config: &Config) -> bool{
+    // 1.5 Nobody else can reach us, and it's small enough to solve exactly.
+    if try_endgame(start, snake_id, config){
+        return false;
+    }
+    // we carry on from the end of the path, where snakes may have died since the start.
+    let leaf = steps.last().unwrap().clone();
+    let mut last_dir = {
+        let body = &leaf.board.snakes.iter().filter(|x| x.id==snake_id).next().unwrap().body;
+        let body0 = body.get(0).unwrap_or(&requests::Point{x: 0, y: 0});
+        // snakes start out curled up on one cell, with no neck to speak of.
+        match body.iter().find(|&piece| piece != body0){
+            Some(body1) => Movement::from_offset(*body0-*body1),
+            None => Movement::Right,
+        }
+    };
+    // 2. Explore the future
+    let mut snake_idx = leaf.board.snakes.iter().position(|snake| snake.id==snake_id).unwrap();
+    let mut stale_snake_idx = snake_idx;
+    let mut depth = 0;
+    while depth<config.search.explore_depth{
+        let step_arc = steps.last().unwrap().clone();
+        if *step_arc.score.lock().unwrap() == 0 {
+            break;
+        }
+        // 2.1 Find a direction to move that isn't taken.
+        let (dir, mut step) = loop{
+            // 2.1.1 Choose a direction to move
+            let mut dir = if rng.gen() { *Movement::ALL.choose(rng).unwrap() } else { last_dir };
+            // Go straight instead of biting our neck
+            if dir==last_dir.opposite(){
+                dir = last_dir;
+            }
+            // 2.1.2 Own that direction
+            if let Ok(step) = step_arc.dirs[dir.to_int()].try_lock(){
+                break (dir, step);
+            }
+            // 2.1.3 Failed! let's not be too hoggish
+            std::thread::yield_now();
+        };
+        if step.is_some() { continue; }
+        depth+=1;
+        // 2.2 Simulate step
+        let (new_board, new_idx) = simulate(&step_arc.board, snake_idx, dir, rng, config);
+        snake_idx = new_idx;
+        // 2.5 Save
+        // 2.5.2 Apply child count
+        for step in steps.iter().rev(){
+            *step.children.lock().unwrap() += 1;
+        }
+        // no future for dead snakes.
+        if snake_idx > 1000 {
+            break;
+        }
+        stale_snake_idx = snake_idx;
+        // 2.5.1 Create SnekStep
+        let generation = 1 + start.generation + depth;
+        let new_step = Arc::new(SnekStep::new(generation, new_board));
+        // Push step into the history
+        steps.push(new_step.clone());
+        // Put step into datastructure
+        *step = Some(new_step);
+        drop (step);
+        drop (step_arc);
+        last_dir = dir;
+    }
+    // 2.4 Apply heuristics to calculate score
+    let generation = start.generation + steps.len() as u32;
+    let score = evaluate(&steps.last().unwrap().board, stale_snake_idx, generation, config);
+    // 2.5.4 Apply score
+    for step in steps.iter().rev(){
+        let mut sscore = step.score.lock().unwrap();
+        if *sscore < score {
+            *sscore = score;
+        }else{
+            break;
+        }
+    }
+    true
+}
+
+fn root(turn: &requests::Turn, config: &Config) -> Arc<SnekStep> {
+    let mut data = SnekStep::new(
+        1, turn.board.clone()
+    );
+    // once a turn, rather than a flood fill on every trip into the future.
+    let me = turn.board.snakes.iter().position(|snake| snake.id == turn.you.id);
+    let region = me.and_then(|me| endgame::sealed_region(&turn.board, me));
+    data.sealed = region.map_or(false, |size| size <= config.endgame.max_region);
+    *data.score.lock().unwrap() = 1;
+    Arc::new(data)
+}
+
+// Swap in the new turn for the workers to chew on.
+pub fn prepare_data(turn: &requests::Turn, config: &Config) -> Arc<SnekStep> {
+    let data = root(turn, config);
+    CURRENT_STEP.lock().unwrap().insert(key(turn), data.clone()).map(|r|
+        *r.historic.lock().unwrap() = true
+    );
+    data
+}
+
+// Put the workers for this game out of their misery.
+pub fn finish(turn: &requests::Turn) {
+    if let Some(r) = CURRENT_STEP.lock().unwrap().remove(&key(turn)){
+        *r.historic.lock().unwrap() = true;
+    }
+}
+
+// Start as many threads as the config asks for thinking about this game in the background.
+pub fn spawn_workers(turn: &requests::Turn, config: &Shared) -> Arc<SnekStep> {
+    let data = prepare_data(turn, &config.load());
+    hire_workers(turn, 0..config.load().search.workers, config);
+    data
+}
+
+// Start workers numbered `workers` on a game that's already been prepared.
+pub fn hire_workers(turn: &requests::Turn, workers: std::ops::Range<u32>, config: &Shared) {
+    for worker in workers{
+        let key = key(turn);
+        let config = config.clone();
+        std::thread::spawn(move || {
+            let _working = metrics::Working::start();
+            // the turn's move still gets made from whatever the other workers found.
+            if panic::catch_unwind(AssertUnwindSafe(|| thread_work(&key, worker, &config))).is_err(){
+                metrics::panicked();
+            }
+        });
+    }
+}
+
+pub fn is_solo(turn: &requests::Turn) -> bool{
+    turn.board.snakes.len() == 1 && turn.board.snakes[0].id == turn.you.id
+}
+
+// How long to think for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Budget{
+    // Until this many futures have been looked at.
+    Nodes(u32),
+    Time(Duration),
+    // Every move of every snake this many turns deep, however long it takes.
+    Depth(u32),
+}
+
+pub struct Decision{
+    pub movement: Movement,
+    // What each first move scored, indexed by Movement::to_int
+    pub scores: [Option<u32>; 4],
+    // How many futures were looked at after each first move, indexed the same
+    pub visits: [u32; 4],
+    // The move we picked, then the best line the search found after it
+    pub pv: Vec<Movement>,
+    pub futures: u32,
+    // Safety overruled the best score, or there wasn't one
+    pub fallback: bool,
+}
+
+impl Decision{
+    pub fn score(&self) -> u32{
+        self.scores[self.movement.to_int()].unwrap_or(0)
+    }
+}
+
+// Pick a move from whatever's been found under start so far.
+pub fn decide(turn: &requests::Turn, start: &SnekStep) -> Decision{
+    let mut scores = [None; 4];
+    let mut visits = [0; 4];
+    let (movement, fallback) = if is_solo(turn){
+        // Last snake standing, random rollouts would only trap us. The plan's only a preference,
+        // same as a search score: a safe move still beats certain death.
+        let plan = solo::plan(&turn.board, 0);
+        let mut preferred = [None; 4];
+        preferred[plan.to_int()] = Some(1);
+        let movement = safety::pick(&turn.board, 0, &preferred);
+        (movement, movement != plan)
+    }else{
+        // a panicking worker poisons its locks, the scores are still worth reading.
+        for (dir, mutex) in start.dirs.iter().enumerate(){
+            if let Some(ref step) = *mutex.lock().unwrap_or_else(|e| e.into_inner()) {
+                scores[dir] = Some(*step.score.lock().unwrap_or_else(|e| e.into_inner()));
+                visits[dir] = *step.children.lock().unwrap_or_else(|e| e.into_inner());
+            }
+        }
+        // Whatever the search thinks (or if it came up empty), don't pick certain death over a safe move.
+        let movement = match turn.board.snakes.iter().position(|snake| snake.id == turn.you.id){
+            Some(me) => safety::pick(&turn.board, me, &scores),
+            None => Movement::Right,
+        };
+        let best = scores.iter().cloned().max().unwrap_or(None);
+        (movement, best.is_none() || scores[movement.to_int()] < best)
+    };
+    let mut pv = vec![movement];
+    let mut step = start.dirs[movement.to_int()].lock().unwrap_or_else(|e| e.into_inner()).clone();
+    while let Some(next) = step{
+        step = best_child(&next).map(|(dir, child)| {
+            pv.push(dir);
+            child
+        });
+    }
+    Decision{
+        movement,
+        scores,
+        visits,
+        pv,
+        fallback,
+        futures: *start.children.lock().unwrap_or_else(|e| e.into_inner()),
+    }
+}
+
+// The highest scoring move from here, first one on ties.
+fn best_child(step: &SnekStep) -> Option<(Movement, Arc<SnekStep>)>{
+    let mut best: Option<(u32, Movement, Arc<SnekStep>)> = None;
+    for (dir, child) in step.expanded(){
+        let score = child.score();
+        if best.as_ref().map(|best| best.0) < Some(score){
+            best = Some((score, dir, child));
+        }
+    }
+    best.map(|(_, dir, child)| (dir, child))
+}
+
+// One of the moves from the root, as the debug API shows it.
+#[derive(Serialize, PartialEq, Debug, Clone)]
+pub struct Branch{
+    #[serde(rename = "move")]
+    pub movement: Movement,
+    pub score: u32,
+    // futures looked at after this move
+    pub visits: u32,
+    // moves looked at from the board after it
+    pub children: usize,
+}
+
+// A step along the principal variation, with the board the search expects.
+#[derive(Serialize, PartialEq, Debug, Clone)]
+pub struct Predicted{
+    #[serde(rename = "move")]
+    pub movement: Movement,
+    pub score: u32,
+    pub visits: u32,
+    pub board: Board,
+}
+
+// Everything the workers have found so far for one of our snakes.
+#[derive(Serialize, PartialEq, Debug, Clone)]
+pub struct Inspection{
+    pub snake: String,
+    pub futures: u32,
+    // SnekSteps in the tree, the root included
+    pub tree_size: usize,
+    // the turn being searched
+    pub board: Board,
+    pub branches: Vec<Branch>,
+    pub pv: Vec<Predicted>,
+}
+
+// Paths go thousands of steps deep, too deep to recurse down.
+fn tree_size(root: &Arc<SnekStep>) -> usize{
+    let mut size = 0;
+    let mut todo = vec![root.clone()];
+    while let Some(step) = todo.pop(){
+        size += 1;
+        todo.extend(step.expanded().into_iter().map(|(_, child)| child));
+    }
+    size
+}
+
+pub fn inspect(snake: &str, root: &Arc<SnekStep>) -> Inspection{
+    let branches = root.expanded().into_iter().map(|(dir, child)| Branch{
+        movement: dir,
+        score: child.score(),
+        visits: child.visits(),
+        children: child.expanded().len(),
+    }).collect();
+    let mut pv = vec![];
+    let mut step = root.clone();
+    while let Some((dir, child)) = best_child(&step){
+        pv.push(Predicted{
+            movement: dir,
+            score: child.score(),
+            visits: child.visits(),
+            board: child.board.clone(),
+        });
+        step = child;
+    }
+    Inspection{
+        snake: snake.to_string(),
+        futures: root.visits(),
+        tree_size: tree_size(root),
+        board: root.board.clone(),
+        branches,
+        pv,
+    }
+}
+
+// The root the workers are searching from for each of our snakes in this game, if it's being played.
+pub fn live_roots(game_id: &str) -> Vec<(String, Arc<SnekStep>)>{
+    CURRENT_STEP.lock().unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|((game, _), _)| game.id == game_id)
+        .map(|((_, snake), root)| (snake.clone(), root.clone()))
+        .collect()
+}
+
+// Every game being searched right now.
+pub fn live_games() -> Vec<String>{
+    let mut games: Vec<String> = CURRENT_STEP.lock().unwrap_or_else(|e| e.into_inner())
+        .keys()
+        .map(|(game, _)| game.id.clone())
+        .collect();
+    games.sort();
+    games.dedup();
+    games
+}
+
+pub fn inspect_game(game_id: &str) -> Vec<Inspection>{
+    live_roots(game_id).iter().map(|(snake, root)| inspect(snake, root)).collect()
+}
+
+// FNV-1a, which unlike std's hasher won't change between builds.
+pub fn fnv1a(bytes: &[u8]) -> u64{
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+// Randomness for thinking about a turn: the same game, snake and turn always get the same.
+pub fn seeded(turn: &requests::Turn) -> StdRng{
+    let name = format!("{}/{}/{}", turn.game.id, turn.you.id, turn.turn);
+    StdRng::seed_from_u64(fnv1a(name.as_bytes()))
+}
+
+// Search on this thread alone, for when we want an answer without the server.
+// Given the same rng and a node or depth budget, the answer is always the same.
+pub fn think<R: Rng>(turn: &requests::Turn, budget: Budget, config: &Config, rng: &mut R) -> Decision{
+    decide(turn, &grow(turn, budget, config, rng))
+}
+
+// The tree think decides from, for when we want to look at it.
+pub fn grow<R: Rng>(turn: &requests::Turn, budget: Budget, config: &Config, rng: &mut R) -> Arc<SnekStep>{
+    let start = root(turn, config);
+    if is_solo(turn){
+        // decide plans this one on its own.
+    }else if let Budget::Depth(depth) = budget{
+        let me = turn.board.snakes.iter().position(|snake| snake.id == turn.you.id).unwrap();
+        if !try_endgame(&start, &turn.you.id, config){
+            expand(&start, me, depth, config);
+        }
+    }else{
+        let mut steps = vec![start.clone()];
+        let began = Instant::now();
+        loop{
+            let done = match budget{
+                Budget::Nodes(nodes) => *start.children.lock().unwrap() >= nodes,
+                Budget::Time(time) => began.elapsed() >= time,
+                Budget::Depth(_) => true,
+            };
+            if done || !explore(&start, &mut steps, &turn.you.id, rng, config){
+                break;
+            }
+        }
+    }
+    start
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{self, snake};
+    use crate::requests::{Point, Snake, Turn};
+
+    fn turn(snakes: Vec<Snake>) -> Turn {
+        fixtures::turn("game-id-string", 4, fixtures::board(7, 7, snakes, &[(1, 1)]))
+    }
+
+    #[test]
+    fn thinks_within_budget() {
+        let turn = turn(vec![
+            snake("us", 90, &[(6, 3), (5, 3), (4, 3)]),
+            snake("them", 90, &[(0, 0), (0, 1), (0, 2)]),
+        ]);
+        let decision = think(&turn, Budget::Nodes(200), &Config::default(), &mut seeded(&turn));
+        assert!(decision.futures >= 200);
+        assert!(decision.visits.iter().sum::<u32>() <= decision.futures);
+        assert_eq!(decision.pv[0], decision.movement);
+        assert_ne!(decision.movement, Movement::Right);
+        assert_ne!(decision.movement, Movement::Left);
+    }
+
+    #[test]
+    fn same_seed_same_decision() {
+        let turn = turn(vec![
+            snake("us", 90, &[(3, 3), (3, 4), (3, 5)]),
+            snake("them", 90, &[(5, 1), (5, 2), (5, 3), (5, 4)]),
+        ]);
+        let config = Config::default();
+        let a = think(&turn, Budget::Nodes(500), &config, &mut seeded(&turn));
+        let b = think(&turn, Budget::Nodes(500), &config, &mut seeded(&turn));
+        assert_eq!(a.movement, b.movement);
+        assert_eq!(a.scores, b.scores);
+        assert_eq!(a.futures, b.futures);
+    }
+
+    #[test]
+    fn depth_budget_looks_at_everything() {
+        let turn = turn(vec![
+            snake("us", 90, &[(3, 3), (3, 4), (3, 5)]),
+            snake("them", 90, &[(0, 0), (0, 1), (0, 2)]),
+        ]);
+        let config = Config::default();
+        let decision = think(&turn, Budget::Depth(2), &config, &mut seeded(&turn));
+        // Down bites our neck. The other three meet all four of their replies, three of which kill
+        // them, then it's our four moves against their four, or our four alone once they're gone.
+        assert_eq!(decision.visits, [16, 16, 16, 0]);
+        assert_eq!(decision.futures, 4 * 4 + 3 * (16 + 3 * 4));
+        assert_eq!(decision.scores[Movement::Down.to_int()], None);
+        assert_eq!(decision.pv.len(), 2);
+        assert_eq!(decision.pv[0], decision.movement);
+        let again = think(&turn, Budget::Depth(2), &config, &mut seeded(&turn));
+        assert_eq!(again.scores, decision.scores);
+        assert_eq!(again.pv, decision.pv);
+    }
+
+    #[test]
+    fn depth_budget_expects_the_worst() {
+        // Right is where a longer snake's head can meet ours, whether or not it would.
+        let turn = turn(vec![
+            snake("us", 90, &[(3, 3), (3, 4), (3, 5)]),
+            snake("them", 90, &[(5, 3), (6, 3), (6, 4), (6, 5)]),
+        ]);
+        let decision = think(&turn, Budget::Depth(1), &Config::default(), &mut seeded(&turn));
+        assert_eq!(decision.scores[Movement::Right.to_int()], Some(0));
+        assert!(decision.scores[Movement::Up.to_int()] > Some(0));
+        assert_ne!(decision.movement, Movement::Right);
+    }
+
+    #[test]
+    fn sealed_turns_go_to_the_solver() {
+        // Walled into the two left columns, well away from their head.
+        let turn = turn(vec![
+            snake("us", 90, &[(0, 1), (0, 0), (1, 0)]),
+            snake("them", 90, &[(3, 6), (2, 6), (2, 5), (2, 4), (2, 3), (2, 2), (2, 1), (2, 0), (3, 0)]),
+        ]);
+        let mut config = Config::default();
+        assert!(root(&turn, &config).sealed);
+        let decision = think(&turn, Budget::Nodes(100), &config, &mut seeded(&turn));
+        assert!(decision.score() > ENDGAME_SCORE);
+
+        config.endgame.max_region = 5;
+        assert!(!root(&turn, &config).sealed);
+        let decision = think(&turn, Budget::Nodes(100), &config, &mut seeded(&turn));
+        assert!(decision.score() < ENDGAME_SCORE);
+    }
+
+    #[test]
+    fn inspects_live_games() {
+        let mut turn = turn(vec![
+            snake("us", 90, &[(3, 3), (3, 4), (3, 5)]),
+            snake("them", 90, &[(0, 0), (0, 1), (0, 2)]),
+        ]);
+        turn.game.id = "inspected".to_string();
+        let root = prepare_data(&turn, &Config::default());
+        expand(&root, 0, 2, &Config::default());
+
+        let inspections = inspect_game("inspected");
+        assert_eq!(inspections.len(), 1);
+        let inspection = &inspections[0];
+        assert_eq!(inspection.snake, "us");
+        assert_eq!(inspection.futures, 100);
+        assert_eq!(inspection.tree_size, 1 + 3 + 9);
+        assert_eq!(inspection.board, turn.board);
+        assert!(live_games().contains(&"inspected".to_string()));
+        let moves: Vec<Movement> = inspection.branches.iter().map(|branch| branch.movement).collect();
+        assert_eq!(moves, vec![Movement::Right, Movement::Left, Movement::Up]);
+        assert!(inspection.branches.iter().all(|branch| branch.visits == 16 && branch.children == 3));
+        assert_eq!(inspection.pv.len(), 2);
+        let first = &inspection.pv[0];
+        let head = first.board.snakes[0].body[0];
+        assert_eq!(head, Point { x: 3, y: 3 } + first.movement.to_offset());
+
+        finish(&turn);
+        assert!(inspect_game("inspected").is_empty());
+    }
+
+    #[test]
+    fn solo_plans_dont_starve() {
+        // Riding the cycle goes up, but only the food to the right keeps us alive.
+        let mut turn = turn(vec![snake("us", 90, &[
+            (0, 1), (0, 2), (0, 3), (0, 4), (0, 5), (1, 5), (1, 4),
+            (1, 3), (1, 2), (2, 2), (2, 1), (2, 0), (1, 0),
+        ])]);
+        turn.board.width = 6;
+        turn.board.height = 6;
+        turn.board.food = vec![Point { x: 1, y: 1 }];
+        turn.board.snakes[0].health = 1;
+        turn.you = turn.board.snakes[0].clone();
+        assert_eq!(solo::plan(&turn.board, 0), Movement::Up);
+        let decision = think(&turn, Budget::Nodes(10), &Config::default(), &mut seeded(&turn));
+        assert_eq!(decision.movement, Movement::Right);
+        assert!(decision.fallback);
+    }
+
+    #[test]
+    fn workers_follow_the_config() {
+        let mut turn = turn(vec![
+            snake("us", 90, &[(3, 3), (3, 4), (3, 5)]),
+            snake("them", 90, &[(0, 0), (0, 1), (0, 2)]),
+        ]);
+        turn.game.id = "reloaded".to_string();
+        let idle = Config {
+            search: crate::config::Search { workers: 0, ..Default::default() },
+            ..Config::default()
+        };
+        let config = crate::config::shared(idle.clone());
+        let root = spawn_workers(&turn, &config);
+        hire_workers(&turn, 0..1, &config);
+        // workers check the config before every trip, so an idle one never takes any.
+        assert_eq!(root.busy(), 0);
+        assert_eq!(root.visits(), 0);
+
+        let until = |done: &dyn Fn() -> bool| {
+            let began = Instant::now();
+            while !done(){
+                assert!(began.elapsed() < Duration::from_secs(5), "workers never caught up with the config");
+                std::thread::yield_now();
+            }
+        };
+        config.store(Arc::new(Config::default()));
+        until(&|| root.busy() == 1 && root.visits() > 0);
+
+        // a worker only counts itself idle once it's back from its trip.
+        config.store(Arc::new(idle));
+        until(&|| root.busy() == 0);
+        let visits = root.visits();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(root.visits(), visits);
+        finish(&turn);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::aggression;
+use crate::config::{Config, Shouts};
+use crate::recorder::Stats;
+use crate::requests::Turn;
+use crate::responses::Movement;
+use crate::safety::{self, Safety};
+use crate::search::Key;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// What to shout with a move, from templates picked by what's happening in the game.
+// Templates can use {move}, {turn}, {health}, {length}, {snakes}, {opponents} and {stats},
+// the last being what the search made of the move, like "n1234 d7 s262".
+
+// The most the game server passes on.
+pub const MAX_LEN: usize = 256;
+
+// Most dramatic first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Doom,
+    Eliminated,
+    HeadToHead,
+    Hungry,
+    Otherwise,
+}
+
+// A game we haven't moved in for this long is over, whether or not we heard it end.
+const FORGOTTEN_AFTER: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    // How many opponents each game had at our last move (and when that was), to tell when one's gone.
+    static ref OPPONENTS: Mutex<HashMap<Key, (usize, Instant)>> = Mutex::new(HashMap::new());
+}
+
+fn opponents(turn: &Turn) -> usize {
+    turn.board.snakes.iter().filter(|snake| snake.id != turn.you.id).count()
+}
+
+// Everything going on as we make `movement`, most dramatic first and always ending with Otherwise.
+// `before` is how many opponents there were at our last move.
+pub fn events(turn: &Turn, movement: Movement, before: Option<usize>, config: &Config) -> Vec<Event> {
+    let mut events = vec![];
+    if let Some(me) = turn.board.snakes.iter().position(|snake| snake.id == turn.you.id) {
+        let head = turn.board.snakes[me].body[0];
+        if safety::assess(&turn.board, me)[movement.to_int()] == Safety::Death {
+            events.push(Event::Doom);
+        }
+        if before > Some(opponents(turn)) {
+            events.push(Event::Eliminated);
+        }
+        if aggression::winning_collisions(&turn.board, me, &config.aggression).contains(&(head + movement.to_offset())) {
+            events.push(Event::HeadToHead);
+        }
+        if turn.board.snakes[me].health <= config.shouts.low_health {
+            events.push(Event::Hungry);
+        }
+    }
+    events.push(Event::Otherwise);
+    events
+}
+
+fn templates(config: &Shouts, event: Event) -> &[String] {
+    match event {
+        Event::Doom => &config.doom,
+        Event::Eliminated => &config.eliminated,
+        Event::HeadToHead => &config.head_to_head,
+        Event::Hungry => &config.hungry,
+        Event::Otherwise => &config.otherwise,
+    }
+}
+
+// Nodes, depth and score, and whether the safety check overruled the search.
+pub fn compact(stats: &Stats) -> String {
+    let fallback = if stats.fallback { " fallback" } else { "" };
+    format!("n{} d{} s{}{}", stats.futures, stats.depth, stats.score, fallback)
+}
+
+pub fn fill(template: &str, turn: &Turn, movement: Movement, stats: Option<&Stats>) -> String {
+    template
+        .replace("{move}", &movement.to_string())
+        .replace("{turn}", &turn.turn.to_string())
+        .replace("{health}", &turn.you.health.to_string())
+        .replace("{length}", &turn.you.body.len().to_string())
+        .replace("{snakes}", &turn.board.snakes.len().to_string())
+        .replace("{opponents}", &opponents(turn).to_string())
+        .replace("{stats}", &stats.map(compact).unwrap_or_default())
+}
+
+// No control characters, so nobody's terminal gets cleared, and no more than the server will take.
+pub fn clean(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).take(MAX_LEN).collect::<String>().trim().to_string()
+}
+
+// Templates for the most dramatic event that has any, taking turns between them.
+pub fn pick<'a>(events: &[Event], turn: &Turn, config: &'a Shouts) -> Option<&'a str> {
+    let templates = events.iter().map(|&event| templates(config, event)).find(|templates| !templates.is_empty())?;
+    Some(&templates[turn.turn as usize % templates.len()])
+}
+
+pub fn shout(turn: &Turn, movement: Movement, stats: Option<&Stats>, config: &Config) -> String {
+    let key = (turn.game.clone(), turn.you.id.clone());
+    let before = {
+        let mut counts = OPPONENTS.lock().unwrap_or_else(|e| e.into_inner());
+        counts.retain(|_, &mut (_, seen)| seen.elapsed() < FORGOTTEN_AFTER);
+        counts.insert(key, (opponents(turn), Instant::now())).map(|(count, _)| count)
+    };
+    let events = events(turn, movement, before, config);
+    pick(&events, turn, &config.shouts).map_or_else(String::new, |template| clean(&fill(template, turn, movement, stats)))
+}
+
+// The game's over, stop keeping count.
+pub fn forget(turn: &Turn) {
+    let key = (turn.game.clone(), turn.you.id.clone());
+    OPPONENTS.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{self, board, snake};
+
+    // Us in the top left corner, heading down, with a shorter snake just below and to the right.
+    fn turn(health: i32) -> Turn {
+        let snakes = vec![snake("us", health, &[(0, 0), (1, 0), (2, 0)]), snake("them", 90, &[(1, 1), (1, 2)])];
+        fixtures::turn("shouty", 3, board(5, 5, snakes, &[]))
+    }
+
+    #[test]
+    fn notices_events() {
+        let config = Config::default();
+        let turn = turn(90);
+        assert_eq!(events(&turn, Movement::Up, None, &config), vec![Event::Doom, Event::Otherwise]);
+        assert_eq!(events(&turn, Movement::Down, Some(1), &config), vec![Event::HeadToHead, Event::Otherwise]);
+        assert_eq!(events(&turn, Movement::Down, Some(2), &config), vec![Event::Eliminated, Event::HeadToHead, Event::Otherwise]);
+        assert_eq!(events(&self::turn(10), Movement::Left, Some(1), &config), vec![Event::Doom, Event::Hungry, Event::Otherwise]);
+        // Too hungry to go looking for a fight.
+        assert_eq!(events(&self::turn(10), Movement::Down, Some(1), &config), vec![Event::Hungry, Event::Otherwise]);
+    }
+
+    #[test]
+    fn falls_through_to_templates_there_are() {
+        let mut config = Shouts {
+            eliminated: vec![],
+            head_to_head: vec!["a".to_string(), "b".to_string()],
+            ..Shouts::default()
+        };
+        let turn = turn(90);
+        let events = [Event::Eliminated, Event::HeadToHead, Event::Otherwise];
+        assert_eq!(pick(&events, &turn, &config), Some("b"));
+        config.head_to_head = vec![];
+        config.otherwise = vec![];
+        assert_eq!(pick(&events, &turn, &config), None);
+    }
+
+    #[test]
+    fn fills_and_cleans() {
+        let turn = turn(90);
+        let stats = Stats {
+            futures: 1234,
+            score: 262,
+            scores: [None; 4],
+            depth: 7,
+            fallback: true,
+        };
+        let text = fill("{move} on {turn}, {health}/{length}, {opponents} of {snakes}: {stats}", &turn, Movement::Down, Some(&stats));
+        assert_eq!(text, "down on 3, 90/3, 1 of 2: n1234 d7 s262 fallback");
+        assert_eq!(fill("{stats}", &turn, Movement::Down, None), "");
+        assert_eq!(clean("\x1b[1;1H\x1b[2Jhi\nthere\t"), "[1;1H[2Jhithere");
+        assert_eq!(clean(&"é".repeat(300)).chars().count(), MAX_LEN);
+    }
+
+    #[test]
+    fn shouts_when_an_opponent_goes() {
+        let config = Config::default();
+        let mut turn = turn(90);
+        turn.game.id = "shouts_when_an_opponent_goes".to_string();
+        assert_eq!(shout(&turn, Movement::Down, None, &config), "Going down. Try and stop me.");
+        turn.board.snakes.pop();
+        assert_eq!(shout(&turn, Movement::Down, None, &config), "And then there were 1.");
+        forget(&turn);
+        assert!(!OPPONENTS.lock().unwrap().contains_key(&(turn.game.clone(), turn.you.id.clone())));
+    }
+
+    #[test]
+    fn forgets_games_that_never_end() {
+        let config = Config::default();
+        let mut turn = turn(90);
+        turn.game.id = "forgets_games_that_never_end".to_string();
+        let key = (turn.game.clone(), turn.you.id.clone());
+        shout(&turn, Movement::Down, None, &config);
+        let long_ago = Instant::now().checked_sub(FORGOTTEN_AFTER).unwrap();
+        OPPONENTS.lock().unwrap().get_mut(&key).unwrap().1 = long_ago;
+        // Any move in any game clears it out, and it starts counting again.
+        let mut other = turn.clone();
+        other.game.id = "forgets_games_that_never_end, another".to_string();
+        shout(&other, Movement::Down, None, &config);
+        assert!(!OPPONENTS.lock().unwrap().contains_key(&key));
+        forget(&other);
+    }
+}
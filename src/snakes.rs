@@ -0,0 +1,238 @@
+use crate::config::Mount;
+use crate::logging;
+use crate::metrics;
+use crate::recorder::Stats;
+use crate::requests::{Game, Turn};
+use crate::responses::{Movement, Start};
+use crate::safety;
+use crate::strategy::{self, Strategy};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+type Playing = Arc<Mutex<Box<dyn Strategy>>>;
+
+// Every snake the server plays, by path, and a strategy for each game they're in.
+pub struct Snakes {
+    mounts: HashMap<String, Mount>,
+    games: Mutex<HashMap<(String, Game, String), Playing>>,
+}
+
+// "/tree/" and "/tree" are the same place, "" is the root.
+fn normalize(path: &str) -> String {
+    format!("/{}", path.trim_matches('/'))
+}
+
+impl Snakes {
+    pub fn new(mounts: &[Mount]) -> Result<Self, String> {
+        let mut by_path = HashMap::new();
+        for mount in mounts {
+            strategy::check(&mount.strategy)?;
+            if by_path.insert(normalize(&mount.path), mount.clone()).is_some() {
+                return Err(format!("two snakes at {}", mount.path));
+            }
+        }
+        Ok(Snakes {
+            mounts: by_path,
+            games: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.mounts.keys().cloned().collect()
+    }
+
+    // The strategy playing this game, made (and started) if there isn't one yet.
+    // Starting the same game again carries on with the one there is.
+    fn playing(&self, path: &str, turn: &Turn) -> Option<Playing> {
+        let mount = self.mounts.get(path)?;
+        let key = (path.to_string(), turn.game.clone(), turn.you.id.clone());
+        if let Some(playing) = self.games.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+            return Some(playing.clone());
+        }
+        // Starting can mean a request to another server or a new process, which other games shouldn't wait on.
+        let mut strategy = strategy::serving(&mount.strategy).ok()?;
+        strategy.on_start(turn);
+        let playing = Arc::new(Mutex::new(strategy));
+        let mut games = self.games.lock().unwrap_or_else(|e| e.into_inner());
+        // Another request for the same game may have got there first.
+        let playing = games.entry(key).or_insert(playing).clone();
+        metrics::active_games(games.len());
+        Some(playing)
+    }
+
+    pub fn start(&self, path: &str, turn: &Turn) -> Option<Start> {
+        let path = normalize(path);
+        self.playing(&path, turn)?;
+        let mount = &self.mounts[&path];
+        Some(Start::new(mount.color.clone(), mount.head, mount.tail))
+    }
+
+    pub fn movement(&self, path: &str, turn: &Turn) -> Option<(Movement, Option<Stats>)> {
+        let playing = self.playing(&normalize(path), turn)?;
+        let lock = || playing.lock().unwrap_or_else(|e| e.into_inner());
+        let chosen = panic::catch_unwind(AssertUnwindSafe(|| {
+            // The game's other requests can have the strategy while it thinks.
+            let wait = lock().ponder(turn);
+            std::thread::sleep(wait);
+            let mut strategy = lock();
+            (strategy.choose_move(turn), strategy.stats())
+        }));
+        match chosen {
+            Ok((movement, stats)) => Some((movement, stats)),
+            // A bug in the search shouldn't cost us the game, when a safe move will do.
+            Err(_) => {
+                logging::warn("panicked choosing a move").turn(turn).field("path", path).emit();
+                metrics::panicked();
+                metrics::fallback();
+                let me = turn.board.snakes.iter().position(|snake| snake.id == turn.you.id);
+                let movement = me.map_or(Movement::Right, |me| safety::pick(&turn.board, me, &[None; 4]));
+                Some((movement, None))
+            }
+        }
+    }
+
+    pub fn end(&self, path: &str, turn: &Turn) -> bool {
+        let path = normalize(path);
+        if !self.mounts.contains_key(&path) {
+            return false;
+        }
+        let key = (path, turn.game.clone(), turn.you.id.clone());
+        let playing = {
+            let mut games = self.games.lock().unwrap_or_else(|e| e.into_inner());
+            let playing = games.remove(&key);
+            metrics::active_games(games.len());
+            playing
+        };
+        if let Some(playing) = playing {
+            playing.lock().unwrap_or_else(|e| e.into_inner()).on_end(turn);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake, turn};
+    use crate::responses::{HeadType, TailType};
+    use crate::search;
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::time::Duration;
+
+    fn mount(path: &str, strategy: &str) -> Mount {
+        Mount {
+            path: path.to_string(),
+            strategy: strategy.to_string(),
+            color: "#00FF00".to_string(),
+            head: HeadType::Bendr,
+            tail: TailType::Curled,
+        }
+    }
+
+    fn alone() -> Turn {
+        turn("game-id-string", 4, board(5, 5, vec![snake("us", 90, &[(0, 1), (0, 2)])], &[(3, 1)]))
+    }
+
+    #[test]
+    fn serves_by_path() {
+        let snakes = Snakes::new(&[mount("/greedy/", "greedy"), mount("safe", "safe")]).unwrap();
+        let turn = alone();
+        let start = snakes.start("/greedy", &turn).unwrap();
+        assert_eq!(start, Start::new("#00FF00".to_string(), HeadType::Bendr, TailType::Curled));
+        assert_eq!(snakes.movement("/greedy", &turn).unwrap().0, Movement::Right);
+        // No /start first is fine too.
+        assert!(snakes.movement("/safe/", &turn).is_some());
+        assert!(snakes.movement("/tree", &turn).is_none());
+        assert!(snakes.end("/greedy", &turn));
+        assert!(!snakes.end("/", &turn));
+    }
+
+    // A snake server that says when a request's arrived, and answers when it's let go.
+    fn held_server() -> (String, Receiver<()>, Sender<()>) {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (arrived, arrivals) = channel();
+        let (release, released) = channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 8192];
+                let _ = stream.read(&mut request);
+                let _ = arrived.send(());
+                let _ = released.recv();
+                let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{{}}");
+            }
+        });
+        (url, arrivals, release)
+    }
+
+    #[test]
+    fn slow_starts_dont_hold_up_other_games() {
+        let (url, arrivals, release) = held_server();
+        let snakes = Arc::new(Snakes::new(&[mount("/slow", &url), mount("/safe", "safe")]).unwrap());
+        let mut slow = alone();
+        slow.game.id = "slow".to_string();
+        let (started, starts) = channel();
+        {
+            let snakes = snakes.clone();
+            std::thread::spawn(move || started.send(snakes.start("/slow", &slow).is_some()));
+        }
+        arrivals.recv().unwrap();
+        // The slow snake's /start is waiting on its server: another game's move still goes ahead.
+        let (moved, movement) = channel();
+        let other = snakes.clone();
+        std::thread::spawn(move || moved.send(other.movement("/safe", &alone()).is_some()));
+        assert_eq!(movement.recv_timeout(Duration::from_secs(5)), Ok(true));
+        assert!(starts.try_recv().is_err());
+        release.send(()).unwrap();
+        assert_eq!(starts.recv(), Ok(true));
+    }
+
+    #[test]
+    fn starting_again_carries_on() {
+        let (url, arrivals, release) = held_server();
+        let snakes = Snakes::new(&[mount("/remote", &url)]).unwrap();
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        assert!(snakes.start("/remote", &alone()).is_some());
+        assert!(snakes.start("/remote", &alone()).is_some());
+        assert_eq!(arrivals.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn ends_while_a_move_thinks() {
+        let snakes = Arc::new(Snakes::new(&[mount("/tree", "tree")]).unwrap());
+        let mut turn = alone();
+        turn.game.id = "ends_while_a_move_thinks".to_string();
+        turn.board.snakes.push(snake("them", 90, &[(4, 4), (4, 3)]));
+        snakes.start("/tree", &turn).unwrap();
+        let key = (turn.game.clone(), turn.you.id.clone());
+        let root = || search::CURRENT_STEP.lock().unwrap().get(&key).cloned();
+        let started = root().unwrap();
+
+        let (moved, movement) = channel();
+        {
+            let (snakes, turn) = (snakes.clone(), turn.clone());
+            std::thread::spawn(move || moved.send(snakes.movement("/tree", &turn).is_some()));
+        }
+        // The move's put its own tree in, and is off waiting for the workers.
+        while root().filter(|root| !Arc::ptr_eq(root, &started)).is_none() {
+            std::thread::yield_now();
+        }
+        assert!(snakes.end("/tree", &turn));
+        assert!(movement.try_recv().is_err());
+        assert_eq!(movement.recv(), Ok(true));
+        assert!(root().is_none());
+    }
+
+    #[test]
+    fn rejects_bad_mounts() {
+        assert!(Snakes::new(&[mount("/", "nonsense")]).is_err());
+        assert!(Snakes::new(&[mount("/", "exec: ")]).is_err());
+        // Only checked, not started.
+        assert!(Snakes::new(&[mount("/", "exec:no-such-engine --fast")]).is_ok());
+        assert!(Snakes::new(&[mount("/a", "safe"), mount("/a/", "greedy")]).is_err());
+    }
+}
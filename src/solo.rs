@@ -0,0 +1,236 @@
+use crate::analysis::Grid;
+use crate::requests::{Board, Point};
+use crate::responses::Movement;
+use crate::safety;
+
+// Room to leave between our head and tail when cutting across the cycle, since we grow as we eat.
+const SHORTCUT_SLACK: u32 = 3;
+
+// A closed tour of the board where every step is to a neighbouring cell.
+// Boards with an odd number of cells can't have one, so those leave out the bottom right
+// corner, (width - 1, height - 1).
+pub struct Cycle {
+    width: i32,
+    tour: Vec<Point>,
+    // Where each cell comes in the tour.
+    order: Vec<Option<u32>>,
+}
+
+impl Cycle {
+    pub fn new(width: i32, height: i32) -> Option<Self> {
+        let tour = if width < 2 || height < 2 {
+            return None;
+        } else if height % 2 == 0 {
+            lawnmower(width, height)
+        } else if width % 2 == 0 {
+            lawnmower(height, width)
+                .into_iter()
+                .map(|p| Point { x: p.y, y: p.x })
+                .collect()
+        } else {
+            with_last_row(width, height)
+        };
+        let mut order = vec![None; (width * height) as usize];
+        for (k, p) in tour.iter().enumerate() {
+            order[(p.x + p.y * width) as usize] = Some(k as u32);
+        }
+        Some(Cycle { width, tour, order })
+    }
+
+    pub fn len(&self) -> u32 {
+        self.tour.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tour.is_empty()
+    }
+
+    pub fn position(&self, pos: Point) -> Option<u32> {
+        if pos.x < 0 || pos.y < 0 || pos.x >= self.width {
+            return None;
+        }
+        self.order.get((pos.x + pos.y * self.width) as usize).cloned().flatten()
+    }
+
+    pub fn next(&self, pos: Point) -> Option<Point> {
+        self.position(pos)
+            .map(|k| self.tour[((k + 1) % self.len()) as usize])
+    }
+
+    // Whether every piece of a body comes right after the next one along the tour,
+    // or sits on it, as a tail does after eating.
+    pub fn follows(&self, body: &[Point]) -> bool {
+        body.windows(2).all(|pair| pair[0] == pair[1] || self.next(pair[1]) == Some(pair[0]))
+    }
+
+    // Steps along the tour from a to b.
+    pub fn distance(&self, a: Point, b: Point) -> Option<u32> {
+        let (a, b) = (self.position(a)?, self.position(b)?);
+        Some((b + self.len() - a) % self.len())
+    }
+}
+
+// Along the top row, back and forth over the rest leaving the first column clear, then up the first column.
+// Needs an even height to end up back at the start.
+fn lawnmower(width: i32, height: i32) -> Vec<Point> {
+    let mut tour: Vec<Point> = (0..width).map(|x| Point { x, y: 0 }).collect();
+    for y in 1..height {
+        if y % 2 == 1 {
+            tour.extend((1..width).rev().map(|x| Point { x, y }));
+        } else {
+            tour.extend((1..width).map(|x| Point { x, y }));
+        }
+    }
+    tour.extend((1..height).rev().map(|y| Point { x: 0, y }));
+    tour
+}
+
+// Odd by odd: mow everything but the last row, then dip into the last row in pairs.
+fn with_last_row(width: i32, height: i32) -> Vec<Point> {
+    let above = height - 2;
+    let base = lawnmower(width, height - 1);
+    let mut tour = Vec::with_capacity((width * height) as usize);
+    for (i, &pos) in base.iter().enumerate() {
+        tour.push(pos);
+        let next = base[(i + 1) % base.len()];
+        if pos.y == above && next.y == above && next.x + 1 == pos.x && next.x % 2 == 0 {
+            tour.push(Point { x: pos.x, y: height - 1 });
+            tour.push(Point { x: next.x, y: height - 1 });
+        }
+    }
+    tour
+}
+
+// Our move when we're the only snake left: ride the cycle, cut across it towards food when
+// the shortcut can't run us into our own body, and otherwise chase our tail.
+// Shortcuts are only safe while the body lies along the cycle, so they wait until it does.
+pub fn plan(board: &Board, me: usize) -> Movement {
+    let us = &board.snakes[me];
+    let head = us.body[0];
+    let tail = *us.body.last().unwrap();
+    let grid = Grid::new(board);
+    let free: Vec<Movement> = Movement::ALL
+        .iter()
+        .cloned()
+        .filter(|m| grid.is_free(head + m.to_offset()))
+        .collect();
+
+    if let Some(cycle) = Cycle::new(board.width, board.height) {
+        // Free cells ahead of us on the cycle, before we'd hit our tail.
+        let room = match cycle.distance(head, tail) {
+            Some(0) => Some(cycle.len()),
+            room => room,
+        };
+        let food = board
+            .food
+            .iter()
+            .filter_map(|&food| cycle.distance(head, food))
+            .min();
+        let on_cycle = cycle.follows(&us.body);
+        let mut best = None;
+        for &dir in free.iter() {
+            let skip = match cycle.distance(head, head + dir.to_offset()) {
+                Some(skip) => skip,
+                None => continue,
+            };
+            let shortcut = match (room, food) {
+                (Some(room), Some(food)) => on_cycle && skip <= food && skip + SHORTCUT_SLACK < room,
+                _ => false,
+            };
+            if skip == 1 || shortcut {
+                best = match best {
+                    Some((_, most)) if most >= skip => best,
+                    _ => Some((dir, skip)),
+                };
+            }
+        }
+        if let Some((dir, _)) = best {
+            return dir;
+        }
+    }
+
+    chase_tail(board, me, &grid, &free)
+}
+
+// Prefer moves that keep a way back to our tail, then whichever leaves the most room.
+// Boxed in, the least bad move.
+fn chase_tail(board: &Board, me: usize, grid: &Grid, free: &[Movement]) -> Movement {
+    let body = &board.snakes[me].body;
+    let (head, tail) = (body[0], grid.index(*body.last().unwrap()));
+    free.iter()
+        .cloned()
+        .max_by_key(|dir| {
+            let next = head + dir.to_offset();
+            let to_tail = match tail {
+                Some(tail) => grid.distances(next)[tail].is_some(),
+                None => false,
+            };
+            (to_tail, grid.reachable(next))
+        })
+        .unwrap_or_else(|| safety::pick(board, me, &[None; 4]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake};
+
+    fn check_cycle(width: i32, height: i32) {
+        let cycle = Cycle::new(width, height).unwrap();
+        let cells = width * height;
+        assert_eq!(cycle.len() as i32, cells - cells % 2);
+        for (k, &pos) in cycle.tour.iter().enumerate() {
+            let next = cycle.tour[(k + 1) % cycle.tour.len()];
+            assert_eq!(pos.distance(next), 1, "{}x{} breaks at {:?}", width, height, pos);
+            assert_eq!(cycle.position(pos), Some(k as u32));
+        }
+    }
+
+    #[test]
+    fn cycles_cover_board() {
+        check_cycle(2, 2);
+        check_cycle(4, 6);
+        check_cycle(6, 5);
+        check_cycle(5, 6);
+        check_cycle(7, 7);
+        check_cycle(11, 11);
+        assert!(Cycle::new(1, 5).is_none());
+        let cycle = Cycle::new(7, 5).unwrap();
+        assert_eq!(cycle.position(Point { x: 6, y: 4 }), None);
+    }
+
+    fn solo(body: &[(i32, i32)], food: &[(i32, i32)]) -> Board {
+        board(6, 6, vec![snake("us", 50, body)], food)
+    }
+
+    #[test]
+    fn follows_cycle_without_food() {
+        let board = solo(&[(2, 0), (1, 0), (0, 0)], &[]);
+        assert_eq!(plan(&board, 0), Movement::Right);
+    }
+
+    #[test]
+    fn cuts_across_to_food() {
+        // The cycle goes all the way along the top row first, food is straight down.
+        let board = solo(&[(2, 0), (1, 0), (0, 0)], &[(2, 2)]);
+        assert_eq!(plan(&board, 0), Movement::Down);
+    }
+
+    #[test]
+    fn no_shortcuts_off_the_cycle() {
+        // Same food, but the body doubles back across the cycle, so a shortcut could cut it off.
+        let board = solo(&[(2, 0), (1, 0), (1, 1), (0, 1)], &[(2, 2)]);
+        assert!(!Cycle::new(6, 6).unwrap().follows(&board.snakes[0].body));
+        assert_eq!(plan(&board, 0), Movement::Right);
+        assert!(Cycle::new(6, 6).unwrap().follows(&[Point { x: 2, y: 0 }, Point { x: 1, y: 0 }, Point { x: 1, y: 0 }]));
+    }
+
+    #[test]
+    fn boxed_in_takes_the_least_bad_move() {
+        // Walled in by our own body, tail and all since it stays put after eating.
+        let mut board = solo(&[(0, 0), (1, 0), (1, 1), (0, 1), (0, 1)], &[]);
+        board.snakes[0].health = 100;
+        let dir = plan(&board, 0);
+        assert_eq!(dir, safety::pick(&board, 0, &[None; 4]));
+    }
+}
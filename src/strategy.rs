@@ -0,0 +1,406 @@
+use crate::bots::Bot;
+use crate::config::{self, Config, Shared, CONFIG};
+use crate::http;
+use crate::logging;
+use crate::protocol::Reply;
+use crate::recorder::Stats;
+use crate::requests::Turn;
+use crate::responses::{self, Movement};
+use crate::safety;
+use crate::search::{self, Budget, Decision, SnekStep};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Something that can play a game of snake.
+pub trait Strategy: Send {
+    fn on_start(&mut self, _turn: &Turn) {}
+    // Get any background thinking about this turn going, and say how long to leave it before
+    // choose_move. The wait is left to the caller, so it needn't hold on to the strategy meanwhile.
+    fn ponder(&mut self, _turn: &Turn) -> Duration {
+        Duration::from_millis(0)
+    }
+    fn choose_move(&mut self, turn: &Turn) -> Movement;
+    fn on_end(&mut self, _turn: &Turn) {}
+    // What went into the last move, for strategies that search.
+    fn stats(&self) -> Option<Stats> {
+        None
+    }
+}
+
+fn stats(decision: &Decision) -> Stats {
+    Stats {
+        futures: decision.futures,
+        score: decision.score(),
+        scores: decision.scores,
+        depth: decision.pv.len() as u32,
+        fallback: decision.fallback,
+    }
+}
+
+// Our tree search, with whatever config it's given.
+pub struct Tree {
+    pub config: Config,
+    pub budget: Budget,
+    last: Option<Stats>,
+}
+
+impl Tree {
+    pub fn new(config: Config, budget: Budget) -> Self {
+        Tree {
+            config,
+            budget,
+            last: None,
+        }
+    }
+}
+
+impl Strategy for Tree {
+    fn choose_move(&mut self, turn: &Turn) -> Movement {
+        let decision = search::think(turn, self.budget, &self.config, &mut search::seeded(turn));
+        self.last = Some(stats(&decision));
+        decision.movement
+    }
+
+    fn stats(&self) -> Option<Stats> {
+        self.last.clone()
+    }
+}
+
+// The tree search the way the server plays it: workers search in the background for the
+// whole game, and each move takes whatever they've found after a while.
+// Changes to the config are picked up as they're made, even mid-game.
+pub struct Pondering {
+    pub config: Shared,
+    // Workers started for this game, some of which may be idle if the config's asked for fewer since.
+    hired: u32,
+    // The turn ponder got going, and where its workers are looking.
+    pondered: Option<(u32, Arc<SnekStep>)>,
+    last: Option<Stats>,
+}
+
+impl Pondering {
+    pub fn new(config: Shared) -> Self {
+        Pondering {
+            config,
+            hired: 0,
+            pondered: None,
+            last: None,
+        }
+    }
+
+    // The workers on to this turn, and how long to give them.
+    fn get_going(&mut self, turn: &Turn, config: &Config) -> (Arc<SnekStep>, Duration) {
+        let start = search::prepare_data(turn, config);
+        self.hire(turn, config);
+        // Give them some time to work.
+        let think = if search::is_solo(turn) { 0 } else { config.search.think_millis };
+        (start, Duration::from_millis(think))
+    }
+
+    // Start more workers if the config wants more than there are.
+    fn hire(&mut self, turn: &Turn, config: &Config) {
+        if config.search.nodes.is_none() && config.search.workers > self.hired {
+            search::hire_workers(turn, self.hired..config.search.workers, &self.config);
+            self.hired = config.search.workers;
+        }
+    }
+}
+
+impl Strategy for Pondering {
+    fn on_start(&mut self, turn: &Turn) {
+        let config = self.config.load_full();
+        if config.search.nodes.is_none() {
+            search::prepare_data(turn, &config);
+            self.hire(turn, &config);
+        }
+    }
+
+    fn ponder(&mut self, turn: &Turn) -> Duration {
+        let config = self.config.load_full();
+        if config.search.nodes.is_some() {
+            return Duration::from_millis(0);
+        }
+        let (start, wait) = self.get_going(turn, &config);
+        self.pondered = Some((turn.turn, start));
+        wait
+    }
+
+    fn choose_move(&mut self, turn: &Turn) -> Movement {
+        let config = self.config.load_full();
+        let decision = match config.search.nodes {
+            Some(nodes) => search::think(turn, Budget::Nodes(nodes), &config, &mut search::seeded(turn)),
+            None => {
+                let start = match self.pondered.take() {
+                    Some((pondered, start)) if pondered == turn.turn => start,
+                    _ => {
+                        let (start, wait) = self.get_going(turn, &config);
+                        std::thread::sleep(wait);
+                        start
+                    }
+                };
+                search::decide(turn, &start)
+            }
+        };
+        self.last = Some(stats(&decision));
+        decision.movement
+    }
+
+    fn on_end(&mut self, turn: &Turn) {
+        search::finish(turn);
+    }
+
+    fn stats(&self) -> Option<Stats> {
+        self.last.clone()
+    }
+}
+
+// The safest move with the most room, and no looking ahead.
+pub struct Safe;
+
+impl Strategy for Safe {
+    fn choose_move(&mut self, turn: &Turn) -> Movement {
+        match turn.board.snakes.iter().position(|snake| snake.id == turn.you.id) {
+            Some(me) => safety::pick(&turn.board, me, &[None; 4]),
+            None => Movement::Right,
+        }
+    }
+}
+
+// A snake server somewhere else, played the way the official engine does:
+// a move that's late (or nonsense) counts as the last one it made.
+pub struct Remote {
+    pub url: String,
+    pub timeout: Duration,
+    last: Movement,
+}
+
+impl Remote {
+    pub fn new(url: &str, timeout: Duration) -> Self {
+        Remote {
+            url: url.trim_end_matches('/').to_string(),
+            timeout,
+            last: Movement::Up,
+        }
+    }
+
+    fn post(&self, endpoint: &str, turn: &Turn) -> std::io::Result<String> {
+        let json = serde_json::to_string(turn).expect("turns always serialize");
+        http::post(&format!("{}/{}", self.url, endpoint), &json, self.timeout)
+    }
+}
+
+impl Strategy for Remote {
+    fn on_start(&mut self, turn: &Turn) {
+        self.last = Movement::Up;
+        if let Err(e) = self.post("start", turn) {
+            logging::warn("didn't start").turn(turn).field("url", &self.url).field("error", e.to_string()).emit();
+        }
+    }
+
+    fn choose_move(&mut self, turn: &Turn) -> Movement {
+        let answer = self.post("move", turn).and_then(|body| {
+            serde_json::from_str::<responses::Move>(&body).map_err(std::io::Error::from)
+        });
+        match answer {
+            Ok(answer) => self.last = answer.movement(),
+            Err(e) => logging::warn("no move, repeating the last")
+                .turn(turn)
+                .field("url", &self.url)
+                .field("move", self.last)
+                .field("error", e.to_string())
+                .emit(),
+        }
+        self.last
+    }
+
+    fn on_end(&mut self, turn: &Turn) {
+        let _ = self.post("end", turn);
+    }
+}
+
+// An engine run as a child process and spoken to over the stdio protocol.
+// Like Remote, a late answer counts as the last move it made.
+pub struct Subprocess {
+    pub command: Vec<String>,
+    pub timeout: Duration,
+    go: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    last: Movement,
+}
+
+impl Subprocess {
+    pub fn new(command: &[String], budget: Budget, timeout: Duration) -> Result<Self, String> {
+        let (program, args) = command.split_first().ok_or("no command to run")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("can't run {}: {}", program, e))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let (send, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in stdout.lines() {
+                match line {
+                    Ok(line) => {
+                        if send.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        let (go, timeout) = match budget {
+            Budget::Time(time) => (format!("go movetime {}", time.as_millis()), timeout + time),
+            Budget::Nodes(nodes) => (format!("go nodes {}", nodes), timeout),
+            Budget::Depth(depth) => (format!("go depth {}", depth), timeout),
+        };
+        Ok(Subprocess {
+            command: command.to_vec(),
+            timeout,
+            go,
+            child,
+            stdin,
+            lines,
+            last: Movement::Up,
+        })
+    }
+
+    fn send(&mut self, line: &str) -> std::io::Result<()> {
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()
+    }
+
+    fn best_move(&mut self, turn: &Turn) -> Result<Movement, String> {
+        // Whatever's left over from a turn it was late for.
+        while self.lines.try_recv().is_ok() {}
+        let position = serde_json::to_string(turn).expect("turns always serialize");
+        self.send(&format!("position {}", position))
+            .and_then(|_| self.send(&self.go.clone()))
+            .map_err(|e| e.to_string())?;
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            let line = match self.lines.recv_timeout(wait) {
+                Ok(line) => line,
+                Err(_) => {
+                    let _ = self.send("stop");
+                    return Err("no answer in time".to_string());
+                }
+            };
+            // Anything that isn't a reply is the engine talking to itself, and an answer for
+            // another turn is one it was late with.
+            match serde_json::from_str(&line) {
+                Ok(Reply::BestMove { movement, turn: answered }) if answered == turn.turn => return Ok(movement),
+                Ok(Reply::Error { message }) => return Err(message),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Strategy for Subprocess {
+    fn on_start(&mut self, turn: &Turn) {
+        self.last = Movement::Up;
+        if let Err(e) = self.send("newgame") {
+            logging::warn("didn't start").turn(turn).field("command", self.command.join(" ")).field("error", e.to_string()).emit();
+        }
+    }
+
+    fn choose_move(&mut self, turn: &Turn) -> Movement {
+        match self.best_move(turn) {
+            Ok(movement) => self.last = movement,
+            Err(e) => logging::warn("no move, repeating the last")
+                .turn(turn)
+                .field("command", self.command.join(" "))
+                .field("move", self.last)
+                .field("error", e)
+                .emit(),
+        }
+        self.last
+    }
+}
+
+impl Drop for Subprocess {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// "tree" is the search with the default config, "tree:<config.json>" runs it with another
+// (like the one an older version shipped with), "safe" never looks ahead,
+// a url is a snake server given `timeout` to answer, "exec:<command>" is an engine run
+// as a subprocess, and anything else is one of the bots.
+pub fn by_name(spec: &str, budget: Budget, timeout: Duration) -> Result<Box<dyn Strategy>, String> {
+    if spec.starts_with("http://") {
+        return Ok(Box::new(Remote::new(spec, timeout)));
+    }
+    if let Ok(bot) = spec.parse::<Bot>() {
+        return Ok(Box::new(bot));
+    }
+    let mut parts = spec.splitn(2, ':');
+    match (parts.next().unwrap_or(""), parts.next()) {
+        ("tree", None) => Ok(Box::new(Tree::new(Config::default(), budget))),
+        ("tree", Some(path)) => Ok(Box::new(Tree::new(Config::from_file(path)?, budget))),
+        ("safe", None) => Ok(Box::new(Safe)),
+        ("exec", Some(command)) => {
+            let command: Vec<String> = command.split_whitespace().map(String::from).collect();
+            Ok(Box::new(Subprocess::new(&command, budget, timeout)?))
+        }
+        _ => Err(format!("unknown strategy {}", spec)),
+    }
+}
+
+// Whether by_name would know what to make of `spec`, without making anything:
+// no processes started, no requests sent.
+pub fn check(spec: &str) -> Result<(), String> {
+    if spec.starts_with("http://") || spec.parse::<Bot>().is_ok() {
+        return Ok(());
+    }
+    let mut parts = spec.splitn(2, ':');
+    match (parts.next().unwrap_or(""), parts.next()) {
+        ("tree", None) | ("safe", None) => Ok(()),
+        ("tree", Some(path)) => Config::from_file(path).map(|_| ()),
+        ("exec", Some(command)) if !command.trim().is_empty() => Ok(()),
+        _ => Err(format!("unknown strategy {}", spec)),
+    }
+}
+
+fn fingerprint(config: &Config) -> u64 {
+    let json = serde_json::to_string(config).expect("configs always serialize");
+    search::fnv1a(json.as_bytes())
+}
+
+// A name for the strategy that changes whenever its config does, for keeping ratings.
+pub fn identity(spec: &str) -> Result<String, String> {
+    let mut parts = spec.splitn(2, ':');
+    let config = match (parts.next().unwrap_or(""), parts.next()) {
+        ("tree", None) => Config::default(),
+        ("tree", Some(path)) => Config::from_file(path)?,
+        _ => return Ok(spec.to_string()),
+    };
+    Ok(format!("{}#{:08x}", spec, fingerprint(&config) as u32))
+}
+
+// Like by_name, but the tree search ponders in the background the way it should on a server,
+// and plain "tree" uses the server's own config.
+pub fn serving(spec: &str) -> Result<Box<dyn Strategy>, String> {
+    let mut parts = spec.splitn(2, ':');
+    match (parts.next().unwrap_or(""), parts.next()) {
+        ("tree", None) => Ok(Box::new(Pondering::new(CONFIG.clone()))),
+        ("tree", Some(path)) => Ok(Box::new(Pondering::new(config::shared(Config::from_file(path)?)))),
+        _ => {
+            let think = Duration::from_millis(config::current().search.think_millis);
+            by_name(spec, Budget::Time(think), think)
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use crate::recorder::{self, Endpoint, Response};
+use crate::requests::{Board, Point};
+use crate::responses::Movement;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{exit, Command, Stdio};
+
+const USAGE: &str = "usage: rusty_snake watch <game.jsonl>";
+
+const HELP: &str = "n/→ next  p/← previous  g/G first/last  o overlay  q quit";
+
+// 256 colour codes for the snakes, in the order they're on the board.
+const COLORS: [u8; 8] = [199, 39, 214, 118, 135, 51, 202, 250];
+
+const RESET: &str = "\x1b[0m";
+const CLEAR: &str = "\x1b[2J\x1b[H";
+
+// One turn to show.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub title: String,
+    pub board: Board,
+    // Whose search the overlay shows.
+    pub you: Option<String>,
+    // Moves made from this board, by snake id, where we know them.
+    pub moves: Vec<(String, Movement)>,
+    // What the search made of each of our moves, indexed by Movement::to_int.
+    pub scores: Option<[Option<u32>; 4]>,
+}
+
+fn arrow(dir: Movement) -> &'static str {
+    match dir {
+        Movement::Right => "→ ",
+        Movement::Left => "← ",
+        Movement::Up => "↑ ",
+        Movement::Down => "↓ ",
+    }
+}
+
+fn color(idx: usize) -> u8 {
+    COLORS[idx % COLORS.len()]
+}
+
+// The board in colour, two characters a cell: snakes in their colours with eyes on their heads,
+// food in red, and with the overlay on, arrows next to our head for the moves the search scored.
+pub fn render(frame: &Frame, overlay: bool) -> String {
+    let board = &frame.board;
+    let mut cells = vec![vec!["\x1b[90m· ".to_string(); board.width.max(0) as usize]; board.height.max(0) as usize];
+    let mut put = |point: Point, cell: String| {
+        if point.x >= 0 && point.y >= 0 && point.x < board.width && point.y < board.height {
+            cells[point.y as usize][point.x as usize] = cell;
+        }
+    };
+    for &food in board.food.iter() {
+        put(food, "\x1b[1;31m()".to_string());
+    }
+    for (idx, snake) in board.snakes.iter().enumerate() {
+        for (part, &piece) in snake.body.iter().enumerate().rev() {
+            let eyes = if part == 0 { "\x1b[1;97moo" } else { "  " };
+            put(piece, format!("\x1b[48;5;{}m{}", color(idx), eyes));
+        }
+    }
+    let us = board.snakes.iter().find(|snake| Some(&snake.id) == frame.you.as_ref());
+    if let (true, Some(us), Some(scores)) = (overlay, us, frame.scores) {
+        let best = scores.iter().cloned().max().unwrap_or(None);
+        for &dir in Movement::ALL.iter() {
+            if let Some(score) = scores[dir.to_int()] {
+                let shade = if Some(score) == best { "1;32" } else { "33" };
+                put(us.body[0] + dir.to_offset(), format!("\x1b[{}m{}", shade, arrow(dir)));
+            }
+        }
+    }
+
+    let mut out = format!("{}\n", frame.title);
+    for row in cells {
+        for cell in row {
+            out += &cell;
+            out += RESET;
+        }
+        out += "\n";
+    }
+    for (idx, snake) in board.snakes.iter().enumerate() {
+        let moved = frame.moves.iter().find(|(id, _)| *id == snake.id).map(|(_, dir)| dir.to_string());
+        let _ = writeln!(
+            out,
+            "\x1b[48;5;{}m  {} {:<20} length {:>3}  health {:>3}  {}",
+            color(idx),
+            RESET,
+            snake.name,
+            snake.body.len(),
+            snake.health,
+            moved.unwrap_or_default()
+        );
+    }
+    if let (true, Some(scores)) = (overlay, frame.scores) {
+        let scores: Vec<String> = Movement::ALL
+            .iter()
+            .map(|dir| match scores[dir.to_int()] {
+                Some(score) => format!("{} {}", dir, score),
+                None => format!("{} -", dir),
+            })
+            .collect();
+        let _ = writeln!(out, "scores: {}", scores.join("  "));
+    }
+    out
+}
+
+// A frame for every move we made in a recorded game.
+pub fn frames(records: &[recorder::Record]) -> Vec<Frame> {
+    records
+        .iter()
+        .filter(|record| record.endpoint == Endpoint::Move)
+        .map(|record| {
+            let turn = &record.request;
+            let moves = match record.response {
+                Response::Move(ref movement) => vec![(turn.you.id.clone(), movement.movement())],
+                _ => vec![],
+            };
+            Frame {
+                title: format!("{} turn {} ({}ms)", turn.game.id, turn.turn, record.millis),
+                board: turn.board.clone(),
+                you: Some(turn.you.id.clone()),
+                moves,
+                scores: record.stats.as_ref().map(|stats| stats.scores),
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Next,
+    Previous,
+    First,
+    Last,
+    Overlay,
+    Quit,
+}
+
+// Whatever keys are in a chunk of input, arrow keys included.
+pub fn keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = vec![];
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let key = match &bytes[idx..] {
+            [27, b'[', b'C', ..] | [27, b'[', b'B', ..] => {
+                idx += 2;
+                Some(Key::Next)
+            }
+            [27, b'[', b'D', ..] | [27, b'[', b'A', ..] => {
+                idx += 2;
+                Some(Key::Previous)
+            }
+            [b'n', ..] | [b'l', ..] | [b' ', ..] => Some(Key::Next),
+            [b'p', ..] | [b'h', ..] | [b'b', ..] => Some(Key::Previous),
+            [b'g', ..] => Some(Key::First),
+            [b'G', ..] => Some(Key::Last),
+            [b'o', ..] => Some(Key::Overlay),
+            [b'q', ..] | [27] | [3, ..] => Some(Key::Quit),
+            _ => None,
+        };
+        keys.extend(key);
+        idx += 1;
+    }
+    keys
+}
+
+// Keys as they're pressed, without echo, until dropped. Does nothing when stdin isn't a terminal.
+// Ctrl-C comes through as a key too rather than killing us, so the terminal always gets put back.
+struct RawMode {
+    saved: Option<String>,
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output().ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
+impl RawMode {
+    fn enter() -> Self {
+        let saved = stty(&["-g"]);
+        if saved.is_some() {
+            stty(&["raw", "-echo"]);
+        }
+        RawMode { saved }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if let Some(ref saved) = self.saved {
+            stty(&[saved]);
+        }
+    }
+}
+
+// Show the frames one at a time, moving through them as keys are pressed.
+pub fn step_through(frames: &[Frame]) -> io::Result<()> {
+    if frames.is_empty() {
+        println!("nothing to show");
+        return Ok(());
+    }
+    let _raw = RawMode::enter();
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut at = 0;
+    let mut overlay = true;
+    let mut buf = [0; 16];
+    loop {
+        // Raw output doesn't go back to the start of the line by itself.
+        let screen = format!("{}[{}/{}] {}{}\n", CLEAR, at + 1, frames.len(), render(&frames[at], overlay), HELP);
+        write!(out, "{}", screen.replace('\n', "\r\n"))?;
+        out.flush()?;
+        let read = input.read(&mut buf)?;
+        if read == 0 {
+            return Ok(());
+        }
+        for key in keys(&buf[..read]) {
+            match key {
+                Key::Next => at = (at + 1).min(frames.len() - 1),
+                Key::Previous => at = at.saturating_sub(1),
+                Key::First => at = 0,
+                Key::Last => at = frames.len() - 1,
+                Key::Overlay => overlay = !overlay,
+                Key::Quit => return Ok(()),
+            }
+        }
+    }
+}
+
+pub fn main(args: &[String]) {
+    let path = match args {
+        [path] => Path::new(path),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+    let shown = recorder::read(path).and_then(|records| step_through(&frames(&records)));
+    if let Err(e) = shown {
+        eprintln!("{}: {}", path.display(), e);
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::requests::fixtures::{board, snake};
+
+    fn frame() -> Frame {
+        Frame {
+            title: "turn 3".to_string(),
+            board: board(4, 3, vec![snake("us", 90, &[(1, 1), (1, 2)]), snake("them", 90, &[(3, 2), (2, 2)])], &[(3, 0)]),
+            you: Some("us".to_string()),
+            moves: vec![("us".to_string(), Movement::Right)],
+            scores: Some([Some(20), Some(10), None, None]),
+        }
+    }
+
+    // What's left without the colours.
+    fn plain(text: &str) -> String {
+        let mut plain = String::new();
+        let mut escaped = false;
+        for c in text.chars() {
+            match (escaped, c) {
+                (false, '\x1b') => escaped = true,
+                (true, 'm') => escaped = false,
+                (true, _) => {}
+                (false, c) => plain.push(c),
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn renders_boards() {
+        let lines: Vec<String> = plain(&render(&frame(), false)).lines().map(String::from).collect();
+        assert_eq!(lines[0], "turn 3");
+        assert_eq!(&lines[1..4], &["· · · ()", "· oo· · ", "·     oo"]);
+        assert!(lines[4].contains("us") && lines[4].ends_with("right"));
+        assert_eq!(lines.len(), 6);
+
+        let lines: Vec<String> = plain(&render(&frame(), true)).lines().map(String::from).collect();
+        assert_eq!(&lines[1..4], &["· · · ()", "← oo→ · ", "·     oo"]);
+        assert_eq!(lines[6], "scores: right 20  left 10  up -  down -");
+    }
+
+    #[test]
+    fn reads_keys() {
+        assert_eq!(keys(b"n\x1b[D\x1b[Co q"), vec![Key::Next, Key::Previous, Key::Next, Key::Overlay, Key::Next, Key::Quit]);
+        assert_eq!(keys(b"\x1b"), vec![Key::Quit]);
+        assert_eq!(keys(b"n\x03"), vec![Key::Next, Key::Quit]);
+        assert_eq!(keys(b"gGx\n"), vec![Key::First, Key::Last]);
+    }
+}
@@ -1,6 +1,12 @@
-use super::rocket;
-use crate::responses;
-use rocket::http::{ContentType, Status};
+use super::{rocket, serve};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rusty_snake::arena::{self, Settings};
+use rusty_snake::config::{Config, Mount};
+use rusty_snake::requests::Turn;
+use rusty_snake::responses::{self, Movement};
+use rusty_snake::strategy::Strategy;
+use rocket::http::{ContentType, Header, Status};
 use rocket::local::Client;
 
 #[test]
@@ -125,3 +131,139 @@ fn end() {
     let response = client.post("/end").dispatch();
     assert_eq!(response.status(), Status::Ok);
 }
+
+#[test]
+fn mounted_snakes() {
+    let config = Config {
+        serve_bots: true,
+        ..Config::default()
+    };
+    let client = Client::new(serve(&config)).expect("Failed to create client instance");
+    let turn = r#"{
+        "game": {"id": "game-id-string"},
+        "turn": 4,
+        "board": {
+            "height": 15,
+            "width": 15,
+            "food": [{"x": 1, "y": 3}],
+            "snakes": [{"id": "snake-id-string", "name": "Sneky Snek", "health": 90, "body": [{"x": 1, "y": 4}]}]
+        },
+        "you": {"id": "snake-id-string", "name": "Sneky Snek", "health": 90, "body": [{"x": 1, "y": 4}]}
+    }"#;
+    let mut response = client.post("/bots/greedy/move").header(ContentType::JSON).body(turn).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let movement: responses::Move = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(movement.movement(), responses::Movement::Up);
+    let response = client.post("/nobody/move").header(ContentType::JSON).body(turn).dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    // Only when asked for.
+    let client = Client::new(rocket()).expect("Failed to create client instance");
+    let response = client.post("/bots/greedy/move").header(ContentType::JSON).body(turn).dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn metrics() {
+    let client = Client::new(rocket()).expect("Failed to create client instance");
+    client.post("/ping").dispatch();
+    let mut response = client.get("/metrics").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let text = response.body_string().unwrap();
+    assert!(text.contains("snek_requests_total{endpoint=\"ping\"}"));
+    assert!(text.contains("# TYPE snek_move_nodes histogram"));
+}
+
+#[test]
+fn debug_unknown_game() {
+    let client = Client::new(rocket()).expect("Failed to create client instance");
+    let response = client.get("/debug/games/no-such-game").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn viewer() {
+    let client = Client::new(rocket()).expect("Failed to create client instance");
+    let mut response = client.get("/viewer").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::HTML));
+    assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), Some("*"));
+    assert!(response.body_string().unwrap().contains("/debug/games"));
+    let response = client.get("/debug/games").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), Some("*"));
+    // Nothing else is for other origins.
+    let response = client.post("/ping").dispatch();
+    assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), None);
+}
+
+#[test]
+fn admin_needs_a_token() {
+    let client = Client::new(rocket()).expect("Failed to create client instance");
+    let response = client.post("/admin/reload").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    let response = client.post("/admin/reload").header(Header::new("Authorization", "Bearer guess")).dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+// One of our mounted snakes, played in the arena without going over the network.
+struct Local {
+    client: Client,
+    path: String,
+}
+
+impl Local {
+    fn new(path: &str) -> Self {
+        let config = Config {
+            serve_bots: true,
+            snakes: vec![Mount { path: "/safe".to_string(), strategy: "safe".to_string(), ..Mount::default() }],
+            ..Config::default()
+        };
+        Local {
+            client: Client::new(serve(&config)).expect("Failed to create client instance"),
+            path: path.to_string(),
+        }
+    }
+
+    fn post(&self, endpoint: &str, turn: &Turn) -> (Status, Option<String>) {
+        let mut response = self
+            .client
+            .post(format!("{}/{}", self.path, endpoint))
+            .header(ContentType::JSON)
+            .body(serde_json::to_string(turn).unwrap())
+            .dispatch();
+        (response.status(), response.body_string())
+    }
+}
+
+impl Strategy for Local {
+    fn on_start(&mut self, turn: &Turn) {
+        assert_eq!(self.post("start", turn).0, Status::Ok);
+    }
+
+    fn choose_move(&mut self, turn: &Turn) -> Movement {
+        let (status, body) = self.post("move", turn);
+        assert_eq!(status, Status::Ok);
+        let answer: responses::Move = serde_json::from_str(&body.unwrap()).unwrap();
+        answer.movement()
+    }
+
+    fn on_end(&mut self, turn: &Turn) {
+        assert_eq!(self.post("end", turn).0, Status::Ok);
+    }
+}
+
+#[test]
+fn arena_against_the_mounted_snakes() {
+    let settings = Settings {
+        width: 7,
+        height: 7,
+        max_turns: 50,
+        ..Settings::default()
+    };
+    let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(Local::new("/safe")), Box::new(Local::new("/bots/greedy"))];
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+    let outcome = arena::play(&settings, &mut strategies, "local-arena", &mut rng);
+    assert!(outcome.turns > 0);
+    assert_eq!(outcome.survived.len(), 2);
+}
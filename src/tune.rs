@@ -0,0 +1,228 @@
+use crate::arena::{self, Lineup, Outcome, Settings};
+use crate::config::{Config, Weights};
+use crate::search::Budget;
+use crate::strategy::Tree;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::exit;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const USAGE: &str = "usage: rusty_snake tune [--iterations N] [--games N] [--nodes N] [--width N] [--height N]
+    [--threads N] [--seed N] [--base CONFIG] [--checkpoint FILE] [--out FILE]";
+
+// Weights are kept between these, in percent.
+const MAX_WEIGHT: f64 = 1000.0;
+
+// SPSA gains, with the usual decay rates. Weights start out nudged by 20 percent,
+// and moving by up to about 12 percent an iteration.
+const A_GAIN: f64 = 2000.0;
+const A_OFFSET: f64 = 10.0;
+const C_GAIN: f64 = 20.0;
+
+// Everything needed to carry on where a run left off.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub iteration: u32,
+    // centre, health, space and hunt, as in Weights.
+    pub theta: [f64; 4],
+    // How much better the nudged-up weights did than the nudged-down ones, each iteration.
+    pub history: Vec<f64>,
+}
+
+impl Checkpoint {
+    pub fn new(seed: u64, weights: Weights) -> Self {
+        Checkpoint {
+            seed,
+            iteration: 0,
+            theta: [
+                weights.centre as f64,
+                weights.health as f64,
+                weights.space as f64,
+                weights.hunt as f64,
+            ],
+            history: vec![],
+        }
+    }
+
+    pub fn weights(&self) -> Weights {
+        weights(&self.theta)
+    }
+}
+
+fn weights(theta: &[f64; 4]) -> Weights {
+    let round = |x: f64| x.clamp(0.0, MAX_WEIGHT).round() as u32;
+    Weights {
+        centre: round(theta[0]),
+        health: round(theta[1]),
+        space: round(theta[2]),
+        hunt: round(theta[3]),
+    }
+}
+
+// From the first snake's point of view: 1 if it outlasted the second every game, -1 if never.
+fn advantage(outcomes: &[Outcome]) -> f64 {
+    let total: f64 = outcomes
+        .iter()
+        .map(|outcome| match outcome.survived[0].cmp(&outcome.survived[1]) {
+            std::cmp::Ordering::Greater => 1.0,
+            std::cmp::Ordering::Equal => 0.0,
+            std::cmp::Ordering::Less => -1.0,
+        })
+        .sum();
+    total / outcomes.len().max(1) as f64
+}
+
+// One round of SPSA: nudge every weight up or down at random, play the weights nudged one way
+// against those nudged the other, and move towards whichever did better.
+// Everything random comes from the seed and iteration, so a resumed run goes the same way.
+pub fn step(checkpoint: &mut Checkpoint, base: &Config, settings: &Settings) -> Result<(), String> {
+    let k = checkpoint.iteration as f64;
+    let mut rng = StdRng::seed_from_u64(checkpoint.seed.wrapping_add(checkpoint.iteration as u64));
+    let delta: Vec<f64> = (0..4).map(|_| if rng.gen() { 1.0 } else { -1.0 }).collect();
+    let a = A_GAIN / (k + 1.0 + A_OFFSET).powf(0.602);
+    let c = C_GAIN / (k + 1.0).powf(0.101);
+
+    let mut plus = checkpoint.theta;
+    let mut minus = checkpoint.theta;
+    for i in 0..4 {
+        plus[i] += c * delta[i];
+        minus[i] -= c * delta[i];
+    }
+    let budget = Budget::Nodes(settings.nodes);
+    let configs = [
+        Config { weights: weights(&plus), ..base.clone() },
+        Config { weights: weights(&minus), ..base.clone() },
+    ];
+    let lineup: Lineup = Arc::new(move || {
+        configs
+            .iter()
+            .map(|config| Box::new(Tree::new(config.clone(), budget)) as Box<_>)
+            .collect()
+    });
+    let settings = Settings {
+        seed: rng.gen(),
+        ..settings.clone()
+    };
+    let outcomes = arena::play_all(&settings, lineup, |_| ())?;
+
+    let better = advantage(&outcomes);
+    for (theta, delta) in checkpoint.theta.iter_mut().zip(delta.iter()) {
+        *theta = (*theta + a * better / (2.0 * c * delta)).clamp(0.0, MAX_WEIGHT);
+    }
+    checkpoint.history.push(better);
+    checkpoint.iteration += 1;
+    Ok(())
+}
+
+fn save<T: Serialize>(value: &T, path: &Path) -> std::io::Result<()> {
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, serde_json::to_string_pretty(value)?)?;
+    std::fs::rename(&partial, path)
+}
+
+fn value<T: FromStr>(arg: Option<&String>) -> T {
+    arena::parsed(arg, USAGE)
+}
+
+pub fn main(args: &[String]) {
+    let mut settings = Settings {
+        games: 20,
+        ..Settings::default()
+    };
+    let mut iterations = 50;
+    let mut base = Config::default();
+    let mut checkpoint_path = "tune-checkpoint.json".to_string();
+    let mut out = "tuned.json".to_string();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--iterations" => iterations = value(args.next()),
+            "--games" => settings.games = value(args.next()),
+            "--nodes" => settings.nodes = value(args.next()),
+            "--width" => settings.width = value(args.next()),
+            "--height" => settings.height = value(args.next()),
+            "--threads" => settings.threads = value(args.next()),
+            "--seed" => settings.seed = value(args.next()),
+            "--checkpoint" => checkpoint_path = value(args.next()),
+            "--out" => out = value(args.next()),
+            "--base" => {
+                base = Config::from_file(&value::<String>(args.next())).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    exit(1);
+                })
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(2);
+            }
+        }
+    }
+
+    let checkpoint_path = Path::new(&checkpoint_path);
+    let mut checkpoint = match std::fs::read_to_string(checkpoint_path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("{}: {}", checkpoint_path.display(), e);
+            exit(1);
+        }),
+        Err(_) => Checkpoint::new(settings.seed, base.weights),
+    };
+    println!("tuning from iteration {}, seed {}", checkpoint.iteration, checkpoint.seed);
+    while checkpoint.iteration < iterations {
+        if let Err(e) = step(&mut checkpoint, &base, &settings) {
+            eprintln!("{}", e);
+            exit(1);
+        }
+        println!(
+            "iteration {:>4}: {:+.2}  {:?}",
+            checkpoint.iteration,
+            checkpoint.history.last().unwrap(),
+            checkpoint.weights()
+        );
+        let tuned = Config {
+            weights: checkpoint.weights(),
+            ..base.clone()
+        };
+        for result in [save(&checkpoint, checkpoint_path), save(&tuned, Path::new(&out))].iter() {
+            if let Err(e) = result {
+                println!("WARNING: Can't save tuning progress: {}", e);
+            }
+        }
+    }
+    println!("tuned config is in {}", out);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keeps_weights_in_range() {
+        let theta = [-5.0, 99.6, 2000.0, 100.0];
+        let weights = weights(&theta);
+        assert_eq!(weights, Weights { centre: 0, health: 100, space: 1000, hunt: 100 });
+    }
+
+    #[test]
+    fn steps_towards_the_winner() {
+        let settings = Settings {
+            width: 7,
+            height: 7,
+            games: 2,
+            nodes: 20,
+            max_turns: 50,
+            threads: 1,
+            seed: 5,
+            ..Settings::default()
+        };
+        let mut checkpoint = Checkpoint::new(5, Weights::default());
+        step(&mut checkpoint, &Config::default(), &settings).unwrap();
+        assert_eq!(checkpoint.iteration, 1);
+        assert_eq!(checkpoint.history.len(), 1);
+        let moved = checkpoint.theta.iter().any(|&theta| theta != 100.0);
+        assert_eq!(moved, checkpoint.history[0] != 0.0);
+    }
+}